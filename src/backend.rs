@@ -0,0 +1,53 @@
+//! Storage backends for the `arf` branch.
+//!
+//! A backend exposes the branch as a tree of files: records, specs, and the
+//! README. [`Worktree`] works on a checked-out `.arf/` directory, while
+//! [`Objects`] reads and writes git objects directly, so it works in bare
+//! clones and checkouts that never ran `arf init`.
+//!
+//! Records can instead be kept as git notes on the commits themselves; see
+//! [`Notes`].
+
+use anyhow::Result;
+
+mod notes;
+mod objects;
+mod worktree;
+
+pub use notes::Notes;
+pub use objects::Objects;
+pub use worktree::Worktree;
+
+/// A pending change to a file on the `arf` branch. `None` removes the file.
+pub type Change = (String, Option<String>);
+
+pub trait Backend: std::fmt::Debug {
+    /// Contents of the file at `path`, relative to the branch root.
+    fn read(&self, path: &str) -> Result<Option<String>>;
+
+    /// All file paths under `dir`, recursively.
+    fn list(&self, dir: &str) -> Result<Vec<String>>;
+
+    /// Names of the immediate children (files and directories) of `dir`.
+    fn children(&self, dir: &str) -> Result<Vec<String>>;
+
+    /// Contents of every file under `dir`.
+    fn read_all(&self, dir: &str) -> Result<Vec<(String, String)>> {
+        let mut files = Vec::new();
+        for path in self.list(dir)? {
+            if let Some(content) = self.read(&path)? {
+                files.push((path, content));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Apply `changes` and commit them to the branch.
+    fn commit(&self, changes: &[Change], message: &str) -> Result<()>;
+
+    /// Whether the branch exists yet.
+    fn exists(&self) -> bool;
+
+    /// The commit the branch is at, if it exists.
+    fn tip(&self) -> Option<String>;
+}
@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+use crate::git;
+
+/// Records attached to commits as git notes.
+///
+/// Each annotated commit has one note holding a TOML stream of its records
+/// (`[[record]]` entries), so `git log --notes=arf` shows them inline.
+#[derive(Debug, Clone)]
+pub struct Notes {
+    repo: PathBuf,
+    notes_ref: String,
+}
+
+impl Notes {
+    pub fn new(repo: impl Into<PathBuf>, notes_ref: impl Into<String>) -> Self {
+        Self {
+            repo: repo.into(),
+            notes_ref: notes_ref.into(),
+        }
+    }
+
+    pub fn notes_ref(&self) -> &str {
+        &self.notes_ref
+    }
+
+    /// Whether any note has been written yet.
+    pub fn exists(&self) -> bool {
+        self.tip().is_some()
+    }
+
+    /// The commit the notes ref is at.
+    pub fn tip(&self) -> Option<String> {
+        git::ref_sha(&self.repo, &self.notes_ref)
+    }
+
+    /// The note attached to `commit`, if any.
+    pub fn read(&self, commit: &str) -> Result<Option<String>> {
+        let output = git::output(
+            &self.repo,
+            &["notes", "--ref", &self.notes_ref, "show", commit],
+        )?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+    }
+
+    /// Every note as `(commit, content)`.
+    pub fn read_all(&self) -> Result<Vec<(String, String)>> {
+        if !self.exists() {
+            return Ok(Vec::new());
+        }
+
+        let out = git::run(&self.repo, &["notes", "--ref", &self.notes_ref, "list"])?;
+        let notes: Vec<(String, String)> = out
+            .lines()
+            .filter_map(|line| {
+                let (blob, commit) = line.split_once(' ')?;
+                Some((blob.to_string(), commit.to_string()))
+            })
+            .collect();
+
+        let blobs: Vec<&str> = notes.iter().map(|(blob, _)| blob.as_str()).collect();
+        let contents = git::read_blobs(&self.repo, &blobs)?;
+
+        Ok(notes
+            .into_iter()
+            .map(|(_, commit)| commit)
+            .zip(contents)
+            .collect())
+    }
+
+    /// Replace the note on `commit`. An empty `content` removes it.
+    pub fn write(&self, commit: &str, content: &str) -> Result<()> {
+        if content.trim().is_empty() {
+            let output = git::output(
+                &self.repo,
+                &[
+                    "notes",
+                    "--ref",
+                    &self.notes_ref,
+                    "remove",
+                    "--ignore-missing",
+                    commit,
+                ],
+            )?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "Failed to remove note: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            return Ok(());
+        }
+
+        // Store the blob ourselves: `notes add -m/-F` would run stripspace on it
+        let blob = git::run_with_input(
+            &self.repo,
+            &["hash-object", "-w", "--stdin"],
+            content.as_bytes(),
+        )?;
+        git::run(
+            &self.repo,
+            &[
+                "notes",
+                "--ref",
+                &self.notes_ref,
+                "add",
+                "-f",
+                "-C",
+                &blob,
+                commit,
+            ],
+        )
+        .map_err(|e| anyhow!("Failed to write note: {}", e))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::{Backend, Change};
+use crate::git;
+
+/// The `arf` branch read and written through git plumbing, with no checkout.
+///
+/// Reads fall back to `refs/remotes/<remote>/<branch>` when there is no local
+/// branch yet, so a fresh clone sees its remote's reasoning immediately. The
+/// first write creates the local branch on top of it.
+#[derive(Debug, Clone)]
+pub struct Objects {
+    repo: PathBuf,
+    branch: String,
+    remote: String,
+}
+
+/// A blob change below some tree: path components and the new blob, if any.
+type TreeChange<'a> = (Vec<&'a str>, Option<&'a str>);
+
+/// An entry in a tree object, as printed by `git ls-tree`.
+#[derive(Debug, Clone)]
+struct TreeEntry {
+    mode: String,
+    kind: String,
+    oid: String,
+}
+
+impl Objects {
+    pub fn new(
+        repo: impl Into<PathBuf>,
+        branch: impl Into<String>,
+        remote: impl Into<String>,
+    ) -> Self {
+        Self {
+            repo: repo.into(),
+            branch: branch.into(),
+            remote: remote.into(),
+        }
+    }
+
+    pub fn repo(&self) -> &Path {
+        &self.repo
+    }
+
+    fn local_ref(&self) -> String {
+        format!("refs/heads/{}", self.branch)
+    }
+
+    fn ls_tree(&self, tree: &str) -> Result<BTreeMap<String, TreeEntry>> {
+        let out = git::run(&self.repo, &["ls-tree", tree])?;
+        let mut entries = BTreeMap::new();
+        for line in out.lines() {
+            let Some((meta, name)) = line.split_once('\t') else {
+                continue;
+            };
+            let mut parts = meta.split(' ');
+            let (Some(mode), Some(kind), Some(oid)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            entries.insert(
+                name.to_string(),
+                TreeEntry {
+                    mode: mode.to_string(),
+                    kind: kind.to_string(),
+                    oid: oid.to_string(),
+                },
+            );
+        }
+        Ok(entries)
+    }
+
+    /// The tree `base` with blobs replaced (`None` deletes), by path from the
+    /// branch root. Used to build merge results.
+    pub(crate) fn tree_with(
+        &self,
+        base: &str,
+        blobs: &[(String, Option<String>)],
+    ) -> Result<String> {
+        let split: Vec<TreeChange> = blobs
+            .iter()
+            .map(|(path, blob)| (path.split('/').collect(), blob.as_deref()))
+            .collect();
+        self.build_tree(Some(base), &split)
+    }
+
+    /// Apply blob changes (`None` deletes) below `base` and return the new tree.
+    fn build_tree(&self, base: Option<&str>, changes: &[TreeChange]) -> Result<String> {
+        let mut entries = match base {
+            Some(tree) => self.ls_tree(tree)?,
+            None => BTreeMap::new(),
+        };
+
+        // Group changes by their first path component
+        let mut blobs: Vec<(&str, Option<&str>)> = Vec::new();
+        let mut subdirs: BTreeMap<&str, Vec<TreeChange>> = BTreeMap::new();
+        for (parts, blob) in changes {
+            match parts.as_slice() {
+                [] => {}
+                [name] => blobs.push((name, *blob)),
+                [dir, rest @ ..] => subdirs.entry(dir).or_default().push((rest.to_vec(), *blob)),
+            }
+        }
+
+        for (name, blob) in blobs {
+            match blob {
+                Some(oid) => {
+                    entries.insert(
+                        name.to_string(),
+                        TreeEntry {
+                            mode: "100644".to_string(),
+                            kind: "blob".to_string(),
+                            oid: oid.to_string(),
+                        },
+                    );
+                }
+                None => {
+                    entries.remove(name);
+                }
+            }
+        }
+
+        for (dir, sub_changes) in subdirs {
+            let sub_base = entries
+                .get(dir)
+                .filter(|e| e.kind == "tree")
+                .map(|e| e.oid.clone());
+            let tree = self.build_tree(sub_base.as_deref(), &sub_changes)?;
+            if self.ls_tree(&tree)?.is_empty() {
+                entries.remove(dir);
+            } else {
+                entries.insert(
+                    dir.to_string(),
+                    TreeEntry {
+                        mode: "040000".to_string(),
+                        kind: "tree".to_string(),
+                        oid: tree,
+                    },
+                );
+            }
+        }
+
+        let input: String = entries
+            .iter()
+            .map(|(name, e)| format!("{} {} {}\t{}\n", e.mode, e.kind, e.oid, name))
+            .collect();
+        git::run_with_input(&self.repo, &["mktree"], input.as_bytes())
+    }
+}
+
+impl Backend for Objects {
+    fn read(&self, path: &str) -> Result<Option<String>> {
+        let Some(tip) = self.tip() else {
+            return Ok(None);
+        };
+        let spec = format!("{}:{}", tip, path);
+        let output = git::output(&self.repo, &["cat-file", "blob", &spec])?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let Some(tip) = self.tip() else {
+            return Ok(Vec::new());
+        };
+        let out = git::run(
+            &self.repo,
+            &["ls-tree", "-r", "--name-only", &tip, "--", dir],
+        )?;
+        Ok(out.lines().map(|l| l.to_string()).collect())
+    }
+
+    fn children(&self, dir: &str) -> Result<Vec<String>> {
+        let Some(tip) = self.tip() else {
+            return Ok(Vec::new());
+        };
+        let spec = format!("{}:{}", tip, dir);
+        let output = git::output(&self.repo, &["ls-tree", "--name-only", &spec])?;
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.to_string())
+            .collect())
+    }
+
+    fn read_all(&self, dir: &str) -> Result<Vec<(String, String)>> {
+        let Some(tip) = self.tip() else {
+            return Ok(Vec::new());
+        };
+
+        // One ls-tree plus one cat-file --batch, rather than a process per file
+        let out = git::run(&self.repo, &["ls-tree", "-r", &tip, "--", dir])?;
+        let mut blobs = Vec::new();
+        for line in out.lines() {
+            if let Some((meta, path)) = line.split_once('\t') {
+                if let Some(oid) = meta.split(' ').nth(2) {
+                    blobs.push((path.to_string(), oid.to_string()));
+                }
+            }
+        }
+
+        let oids: Vec<&str> = blobs.iter().map(|(_, oid)| oid.as_str()).collect();
+        let contents = git::read_blobs(&self.repo, &oids)?;
+
+        Ok(blobs
+            .into_iter()
+            .map(|(path, _)| path)
+            .zip(contents)
+            .collect())
+    }
+
+    fn commit(&self, changes: &[Change], message: &str) -> Result<()> {
+        let local = git::ref_sha(&self.repo, &self.local_ref());
+        let parent = local.clone().or_else(|| self.tip());
+        let base_tree = match parent {
+            Some(ref p) => Some(git::run(
+                &self.repo,
+                &["rev-parse", &format!("{}^{{tree}}", p)],
+            )?),
+            None => None,
+        };
+
+        let mut blob_changes = Vec::new();
+        for (path, content) in changes {
+            let blob = match content {
+                Some(content) => Some(git::run_with_input(
+                    &self.repo,
+                    &["hash-object", "-w", "--stdin"],
+                    content.as_bytes(),
+                )?),
+                None => None,
+            };
+            blob_changes.push((path.as_str(), blob));
+        }
+
+        let split: Vec<TreeChange> = blob_changes
+            .iter()
+            .map(|(path, blob)| (path.split('/').collect(), blob.as_deref()))
+            .collect();
+        let tree = self.build_tree(base_tree.as_deref(), &split)?;
+
+        if base_tree.as_deref() == Some(tree.as_str()) {
+            // Nothing changed
+            return Ok(());
+        }
+
+        let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+        if let Some(ref p) = parent {
+            args.push("-p");
+            args.push(p);
+        }
+        let commit = git::run(&self.repo, &args)?;
+
+        // Compare-and-swap against the local ref so concurrent writers can't
+        // silently drop each other's records.
+        let old = local.unwrap_or_else(|| "0".repeat(commit.len()));
+        let local_ref = self.local_ref();
+        git::run(
+            &self.repo,
+            &["update-ref", "-m", message, &local_ref, &commit, &old],
+        )
+        .map_err(|e| anyhow!("Failed to update {}: {}", local_ref, e))?;
+
+        Ok(())
+    }
+
+    fn exists(&self) -> bool {
+        self.tip().is_some()
+    }
+
+    /// The commit reads come from: the local branch, else the remote's.
+    fn tip(&self) -> Option<String> {
+        let remote_ref = format!("refs/remotes/{}/{}", self.remote, self.branch);
+        git::ref_sha(&self.repo, &self.local_ref())
+            .or_else(|| git::ref_sha(&self.repo, &remote_ref))
+    }
+}
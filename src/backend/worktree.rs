@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use super::{Backend, Change};
+use crate::git;
+
+/// The `arf` branch checked out as a linked worktree.
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    dir: PathBuf,
+}
+
+impl Worktree {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Backend for Worktree {
+    fn read(&self, path: &str) -> Result<Option<String>> {
+        let path = self.dir.join(path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(path)?))
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        walk(&self.dir.join(dir), dir, &mut files);
+        files.sort();
+        Ok(files)
+    }
+
+    fn children(&self, dir: &str) -> Result<Vec<String>> {
+        let Ok(entries) = std::fs::read_dir(self.dir.join(dir)) else {
+            return Ok(Vec::new());
+        };
+        // Like git, ignore directories with no files in them
+        let mut names: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let mut files = Vec::new();
+                !e.path().is_dir() || {
+                    walk(&e.path(), "", &mut files);
+                    !files.is_empty()
+                }
+            })
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name != ".git")
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn commit(&self, changes: &[Change], message: &str) -> Result<()> {
+        for (path, content) in changes {
+            let full = self.dir.join(path);
+            match content {
+                Some(content) => {
+                    if let Some(parent) = full.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&full, content)?;
+                }
+                None => {
+                    if full.exists() {
+                        std::fs::remove_file(&full)?;
+                    }
+                    // Git doesn't track directories; don't leave empty ones behind
+                    let mut parent = full.parent();
+                    while let Some(dir) = parent.filter(|d| *d != self.dir) {
+                        if std::fs::remove_dir(dir).is_err() {
+                            break;
+                        }
+                        parent = dir.parent();
+                    }
+                }
+            }
+        }
+
+        let add = git::worktree_output(&self.dir, &["add", "."])?;
+        if !add.status.success() {
+            return Err(anyhow!("Failed to stage files"));
+        }
+
+        let commit = git::worktree_output(&self.dir, &["commit", "-m", message])?;
+        if !commit.status.success() {
+            // Might be empty, that's ok
+            let stderr = String::from_utf8_lossy(&commit.stderr);
+            let stdout = String::from_utf8_lossy(&commit.stdout);
+            if !stderr.contains("nothing to commit") && !stdout.contains("nothing to commit") {
+                return Err(anyhow!("Failed to commit: {}", stderr));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn exists(&self) -> bool {
+        self.dir.exists()
+    }
+
+    fn tip(&self) -> Option<String> {
+        git::worktree_output(&self.dir, &["rev-parse", "--verify", "--quiet", "HEAD"])
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    }
+}
+
+fn walk(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" {
+            continue;
+        }
+        let rel = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix.trim_end_matches('/'), name)
+        };
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, &rel, files);
+        } else {
+            files.push(rel);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Requiring reasoning for some commits (`arf check`, the `pre-push` hook).
+//!
+//! Rules pick the commits that need a record: ones with a matching
+//! `Co-authored-by` trailer, a matching committer email, or changes under
+//! given paths. A commit matching any rule must have a valid, unretracted
+//! record. Without rules, every commit must. Rules come from the config
+//! (see [`crate::config`]):
+//!
+//! ```text
+//! check.coAuthor   pattern for a Co-authored-by trailer
+//! check.committer  pattern for the committer email
+//! check.path       pathspec the commit touches
+//! ```
+//!
+//! Patterns match case-insensitively. With `*` or `?` they must match the
+//! whole value; otherwise they match anywhere in it.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::coverage::Coverage;
+use crate::git::{self, CommitInfo};
+use crate::store::RecordStore;
+
+/// Which commits need reasoning.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    pub co_authors: Vec<String>,
+    pub committers: Vec<String>,
+    pub paths: Vec<String>,
+}
+
+impl Rules {
+    /// Rules from `check.*` in `config`.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            co_authors: config.get_all("check.coAuthor"),
+            committers: config.get_all("check.committer"),
+            paths: config.get_all("check.path"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.co_authors.is_empty() && self.committers.is_empty() && self.paths.is_empty()
+    }
+}
+
+/// A commit that needs reasoning and has none.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub commit: CommitInfo,
+    /// Why the commit needs reasoning, e.g. `co-author "Bot <bot@x>"`.
+    pub rule: String,
+}
+
+/// Result of [`check`].
+#[derive(Debug, Clone, Default)]
+pub struct CheckResult {
+    /// Commits in the range.
+    pub commits: usize,
+    /// Commits a rule matched.
+    pub required: usize,
+    pub violations: Vec<Violation>,
+}
+
+/// Check the commits `git log <args>` lists against `rules`.
+pub fn check(store: &RecordStore, args: &[&str], rules: &Rules) -> Result<CheckResult> {
+    let repo = store.repo();
+    let coverage = Coverage::compute(store, args)?;
+
+    let touching_paths: HashSet<String> = if rules.paths.is_empty() {
+        HashSet::new()
+    } else {
+        let mut rev_list = vec!["rev-list"];
+        rev_list.extend_from_slice(args);
+        rev_list.push("--");
+        rev_list.extend(rules.paths.iter().map(|p| p.as_str()));
+        git::run(repo, &rev_list)?
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    };
+
+    let mut result = CheckResult {
+        commits: coverage.commits.len(),
+        ..Default::default()
+    };
+    for c in &coverage.commits {
+        let rule = if rules.is_empty() {
+            Some("none configured, so every commit".to_string())
+        } else {
+            matching_rule(repo, &c.commit, rules, &touching_paths)?
+        };
+        let Some(rule) = rule else {
+            continue;
+        };
+
+        result.required += 1;
+        if !c.is_covered() {
+            result.violations.push(Violation {
+                commit: c.commit.clone(),
+                rule,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// The first rule `commit` matches, described for the user.
+fn matching_rule(
+    repo: &Path,
+    commit: &CommitInfo,
+    rules: &Rules,
+    touching_paths: &HashSet<String>,
+) -> Result<Option<String>> {
+    if !rules.co_authors.is_empty() {
+        let message = git::message(repo, &commit.sha)?;
+        for co_author in co_authors(&message) {
+            if rules.co_authors.iter().any(|p| matches(p, &co_author)) {
+                return Ok(Some(format!("co-author \"{}\"", co_author)));
+            }
+        }
+    }
+
+    let email = &commit.committer.email;
+    if rules.committers.iter().any(|p| matches(p, email)) {
+        return Ok(Some(format!("committer {}", email)));
+    }
+
+    if touching_paths.contains(&commit.sha) {
+        return Ok(Some(format!("touches {}", rules.paths.join(", "))));
+    }
+
+    Ok(None)
+}
+
+/// Values of the `Co-authored-by` trailers in a commit message.
+pub fn co_authors(message: &str) -> Vec<String> {
+    message
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("co-authored-by")
+                .then(|| value.trim().to_string())
+        })
+        .collect()
+}
+
+/// Whether `value` matches `pattern` (see the module docs).
+pub fn matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+
+    if !pattern.contains(&'*') && !pattern.contains(&'?') {
+        return pattern.is_empty() || value.windows(pattern.len()).any(|w| w == pattern);
+    }
+    glob(&pattern, &value)
+}
+
+/// `*` matches any run of characters, `?` any one character.
+fn glob(pattern: &[char], value: &[char]) -> bool {
+    let (mut p, mut v) = (0, 0);
+    // Where the last `*` was, and how much of `value` it has swallowed
+    let mut star: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, v));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match star {
+                Some((sp, sv)) => {
+                    p = sp + 1;
+                    v = sv + 1;
+                    star = Some((sp, sv + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_patterns_match_anywhere() {
+        assert!(matches("agent", "Review Agent <agent@example.com>"));
+        assert!(matches("@BOTS.example", "ci@bots.example.com"));
+        assert!(matches("", "anything"));
+        assert!(!matches("copilot", "Review Agent <agent@example.com>"));
+        assert!(!matches("longer than the value", "short"));
+    }
+
+    #[test]
+    fn wildcards_match_the_whole_value() {
+        assert!(matches("*@bots.example", "CI@Bots.Example"));
+        assert!(!matches("*@bots.example", "ci@bots.example.com"));
+        assert!(matches("*bot*", "renovate-bot@example.com"));
+        assert!(matches("a*b*c", "aXXbYYbZZc"));
+        assert!(!matches("a*b*c", "aXXbYYbZZ"));
+        assert!(matches("dev?@example.com", "dev1@example.com"));
+        assert!(!matches("dev?@example.com", "dev@example.com"));
+        assert!(matches("*", ""));
+        assert!(matches("ü*", "Über"));
+    }
+
+    #[test]
+    fn co_author_trailers() {
+        let message = "Fix it\n\nCo-authored-by: A <a@example.com>\nco-authored-by:B\n\
+                       Signed-off-by: C <c@example.com>\n";
+        assert_eq!(co_authors(message), ["A <a@example.com>", "B"]);
+    }
+}
@@ -0,0 +1,530 @@
+//! Settings, in three layers so a team can share defaults and each person
+//! can override them. Later layers win:
+//!
+//! 1. `.arfconfig` at the root of the ARF branch: TOML, committed and synced
+//! 2. git config under `arf.*`, global and then per repository
+//! 3. environment variables `ARF_<KEY>`
+//!
+//! Keys are dotted names. `lint.minWords` is `minWords` under `[lint]` in
+//! `.arfconfig`, `arf.lint.minWords` in git config, and `ARF_LINT_MINWORDS`
+//! in the environment. A list key takes an array in `.arfconfig`, repeated
+//! `git config --add` entries, or a comma-separated environment variable;
+//! the highest layer that sets it replaces the lists below. [`KEYS`] has
+//! every key.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::backend::{Backend, Objects, Worktree};
+use crate::git;
+use crate::store::{RecordStore, Storage};
+use crate::ARF_BRANCH;
+
+/// Remote used when `remote` isn't set.
+pub const DEFAULT_REMOTE: &str = "origin";
+
+/// The shared config file, at the root of the ARF branch.
+pub const CONFIG_FILE: &str = ".arfconfig";
+
+/// Key names `tui.key.*` accepts besides single characters.
+pub const KEY_NAMES: &[&str] = &[
+    "esc",
+    "enter",
+    "tab",
+    "space",
+    "backspace",
+    "up",
+    "down",
+    "left",
+    "right",
+    "pageup",
+    "pagedown",
+    "home",
+    "end",
+];
+
+/// Fields `record.require` can ask for; `context.<key>` works too.
+const REQUIRABLE: &[&str] = &["how", "backup", "outcome", "agent"];
+
+const THEMES: &[&str] = &["dark", "light", "plain"];
+
+/// A setting arf knows about.
+#[derive(Debug, Clone, Copy)]
+pub struct Key {
+    pub name: &'static str,
+    /// Whether the key holds a list.
+    pub list: bool,
+    pub help: &'static str,
+}
+
+const fn key(name: &'static str, list: bool, help: &'static str) -> Key {
+    Key { name, list, help }
+}
+
+pub const KEYS: &[Key] = &[
+    key("agent", false, "agent name for new records"),
+    key("storage", false, "branch or notes (set by 'arf init')"),
+    key(
+        "branch",
+        false,
+        "name of the ARF branch (default arf; not read from .arfconfig)",
+    ),
+    key("remote", false, "remote 'arf sync' uses (default origin)"),
+    key("mirror", true, "more remotes 'arf sync' pushes to"),
+    key(
+        "record.require",
+        true,
+        "fields new records need beyond what and why: how, backup, outcome, agent, context.<key>",
+    ),
+    key(
+        "lint.disable",
+        true,
+        "lint rules to turn off: vague-what, short-what, what-is-why, required, or all",
+    ),
+    key(
+        "lint.vaguePhrase",
+        true,
+        "more phrases that make a 'what' vague",
+    ),
+    key(
+        "lint.minWords",
+        false,
+        "fewest words a 'what' may have (default 3)",
+    ),
+    key(
+        "check.coAuthor",
+        true,
+        "Co-authored-by pattern that needs reasoning",
+    ),
+    key(
+        "check.committer",
+        true,
+        "committer email pattern that needs reasoning",
+    ),
+    key("check.path", true, "pathspec whose commits need reasoning"),
+    key(
+        "tui.theme",
+        false,
+        "colors for 'arf browse': dark, light, or plain",
+    ),
+    key(
+        "tui.key.quit",
+        true,
+        "keys that quit 'arf browse' (default q, esc)",
+    ),
+    key(
+        "tui.key.down",
+        true,
+        "keys that move down (default j, down)",
+    ),
+    key("tui.key.up", true, "keys that move up (default k, up)"),
+    key(
+        "tui.key.diff",
+        true,
+        "keys that cycle the diff view (default d)",
+    ),
+    key(
+        "tui.key.focus",
+        true,
+        "keys that switch panes (default tab, enter)",
+    ),
+    key(
+        "tui.key.pageDown",
+        true,
+        "keys that page the diff down (default f, pagedown)",
+    ),
+    key(
+        "tui.key.pageUp",
+        true,
+        "keys that page the diff up (default b, pageup)",
+    ),
+];
+
+/// The key called `name`, ignoring case.
+pub fn find_key(name: &str) -> Option<&'static Key> {
+    KEYS.iter().find(|k| k.name.eq_ignore_ascii_case(name))
+}
+
+/// Environment variable for `key`: `lint.minWords` is `ARF_LINT_MINWORDS`.
+pub fn env_var(key: &Key) -> String {
+    format!("ARF_{}", key.name.replace('.', "_").to_uppercase())
+}
+
+/// Where a value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    File,
+    Git,
+    Env,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::File => CONFIG_FILE,
+            Source::Git => "git config",
+            Source::Env => "environment",
+        })
+    }
+}
+
+/// The values one layer sets, by lowercase key.
+#[derive(Debug, Clone, Default)]
+struct Layer(BTreeMap<String, Vec<String>>);
+
+impl Layer {
+    fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.0.get(&key.to_lowercase())
+    }
+
+    fn git(repo: &Path) -> Self {
+        let mut layer = Self::default();
+        let Ok(out) = git::output(repo, &["config", "--get-regexp", r"^arf\."]) else {
+            return layer;
+        };
+        // "arf.lint.minwords 3"; a bare key is a boolean true
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, "true"));
+            let Some(key) = key.strip_prefix("arf.") else {
+                continue;
+            };
+            let value = value.trim();
+            if !value.is_empty() {
+                layer
+                    .0
+                    .entry(key.to_lowercase())
+                    .or_default()
+                    .push(value.to_string());
+            }
+        }
+        layer
+    }
+
+    fn env() -> Self {
+        let mut layer = Self::default();
+        for key in KEYS {
+            let Ok(value) = std::env::var(env_var(key)) else {
+                continue;
+            };
+            let values: Vec<String> = if key.list {
+                value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect()
+            } else {
+                vec![value.trim().to_string()]
+            };
+            if values.iter().any(|v| !v.is_empty()) {
+                layer.0.insert(key.name.to_lowercase(), values);
+            }
+        }
+        layer
+    }
+
+    fn file(content: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(content)
+            .map_err(|e| anyhow!("Invalid {}: {}", CONFIG_FILE, e.message()))?;
+        let mut layer = Self::default();
+        layer.flatten("", &table);
+        Ok(layer)
+    }
+
+    fn flatten(&mut self, prefix: &str, table: &toml::Table) {
+        for (name, value) in table {
+            let key = format!("{}{}", prefix, name).to_lowercase();
+            let values = match value {
+                toml::Value::Table(t) => {
+                    self.flatten(&format!("{}.", key), t);
+                    continue;
+                }
+                toml::Value::Array(items) => items.iter().map(scalar).collect(),
+                v => vec![scalar(v)],
+            };
+            self.0.insert(key, values);
+        }
+    }
+}
+
+fn scalar(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub storage: Storage,
+    /// Name of the orphan branch holding records and specs.
+    pub branch: String,
+    /// Remote to sync with.
+    pub remote: String,
+    /// Remotes that get a copy of every push.
+    pub mirrors: Vec<String>,
+    /// Agent name for new records that don't give one.
+    pub agent: Option<String>,
+    /// Fields new records must have, beyond `what` and `why`.
+    pub required: Vec<String>,
+    /// Settings that couldn't be used and were skipped, for the caller to
+    /// report.
+    pub warnings: Vec<String>,
+    file: Layer,
+    git: Layer,
+    env: Layer,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            storage: Storage::Branch,
+            branch: ARF_BRANCH.to_string(),
+            remote: DEFAULT_REMOTE.to_string(),
+            mirrors: Vec::new(),
+            agent: None,
+            required: Vec::new(),
+            warnings: Vec::new(),
+            file: Layer::default(),
+            git: Layer::default(),
+            env: Layer::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Settings for the repository at `repo`, with defaults for anything unset.
+    pub fn load(repo: &Path) -> Result<Self> {
+        let mut config = Self {
+            git: Layer::git(repo),
+            env: Layer::env(),
+            ..Self::default()
+        };
+
+        // The file lives on the branch, so it can't say which branch that is
+        if let Some(branch) = config.get("branch") {
+            config.branch = branch;
+        }
+        if let Some(remote) = config.get("remote") {
+            config.remote = remote;
+        }
+        let content = match git::worktree_of(repo, &config.branch_ref()) {
+            Some(dir) => Worktree::new(dir).read(CONFIG_FILE)?,
+            None => Objects::new(repo, &config.branch, &config.remote).read(CONFIG_FILE)?,
+        };
+        // A bad file shouldn't lock everyone out of the commands that fix it
+        match content.as_deref().map(Layer::file) {
+            None => {}
+            Some(Ok(file)) => config.file = file,
+            Some(Err(e)) => config.warnings.push(format!(
+                "{} (on branch '{}'); ignoring it",
+                e.to_string().replace('\n', ": "),
+                config.branch
+            )),
+        }
+
+        if let Some(storage) = config.get("storage") {
+            match storage.parse() {
+                Ok(storage) => config.storage = storage,
+                Err(e) => config
+                    .warnings
+                    .push(format!("{}; using {}", e, config.storage)),
+            }
+        }
+        if let Some(remote) = config.get("remote") {
+            config.remote = remote;
+        }
+        config.mirrors = config.get_all("mirror");
+        config.agent = config.get("agent");
+        config.required = config.get_all("record.require");
+        Ok(config)
+    }
+
+    /// Ref of the local ARF branch.
+    pub fn branch_ref(&self) -> String {
+        format!("refs/heads/{}", self.branch)
+    }
+
+    /// Remote-tracking ref for `remote`'s ARF branch.
+    pub fn remote_ref(&self, remote: &str) -> String {
+        format!("refs/remotes/{}/{}", remote, self.branch)
+    }
+
+    /// The value of `key` from the highest layer that sets it, and which
+    /// layer that was. A single-valued key set more than once (say, in
+    /// global and repository git config) keeps its last value.
+    pub fn lookup(&self, key: &str) -> Option<(Vec<String>, Source)> {
+        let file = (!key.eq_ignore_ascii_case("branch")).then_some(&self.file);
+        let (mut values, source) = [
+            (Some(&self.env), Source::Env),
+            (Some(&self.git), Source::Git),
+            (file, Source::File),
+        ]
+        .into_iter()
+        .find_map(|(layer, source)| Some((layer?.get(key)?.clone(), source)))?;
+        if find_key(key).is_some_and(|k| !k.list) {
+            values = values.split_off(values.len() - 1);
+        }
+        Some((values, source))
+    }
+
+    /// The value of `key`; the last one if it is set more than once.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_all(key).pop()
+    }
+
+    /// Every value of a list `key`.
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        self.lookup(key).map(|(v, _)| v).unwrap_or_default()
+    }
+
+    /// Every key some layer sets, with its value and source, sorted. Known
+    /// keys are spelled as in [`KEYS`].
+    pub fn list(&self) -> Vec<(String, Vec<String>, Source)> {
+        let mut names: Vec<String> = Vec::new();
+        for layer in [&self.file, &self.git, &self.env] {
+            for key in layer.0.keys() {
+                let name = find_key(key).map_or_else(|| key.clone(), |k| k.name.to_string());
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let (values, source) = self.lookup(&name)?;
+                Some((name, values, source))
+            })
+            .collect()
+    }
+}
+
+/// Check that `value` is something `key` accepts.
+pub fn check_value(key: &Key, value: &str) -> Result<()> {
+    let ok = match key.name {
+        "storage" => return value.parse::<Storage>().map(|_| ()),
+        "lint.minWords" => value.parse::<usize>().is_ok(),
+        "tui.theme" => THEMES.contains(&value),
+        "record.require" => {
+            REQUIRABLE.contains(&value)
+                || value
+                    .strip_prefix("context.")
+                    .is_some_and(|k| !k.is_empty())
+        }
+        name if name.starts_with("tui.key.") => {
+            value.chars().count() == 1 || KEY_NAMES.contains(&value.to_lowercase().as_str())
+        }
+        _ => !value.is_empty(),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "'{}' is not a valid {}: {}",
+            value,
+            key.name,
+            key.help
+        ))
+    }
+}
+
+/// Where `arf config set` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// This repository's git config.
+    Local,
+    /// The user's global git config.
+    Global,
+    /// `.arfconfig` on the ARF branch.
+    Shared,
+}
+
+/// Set `key` to `values` in `scope`, or remove it when `values` is empty.
+/// With `add`, list values are appended instead of replacing.
+pub fn set(
+    store: &RecordStore,
+    scope: Scope,
+    key: &Key,
+    values: &[String],
+    add: bool,
+) -> Result<()> {
+    for value in values {
+        check_value(key, value)?;
+    }
+    if values.len() > 1 && !key.list {
+        return Err(anyhow!("{} takes a single value", key.name));
+    }
+
+    match scope {
+        Scope::Local | Scope::Global => {
+            let git_key = format!("arf.{}", key.name);
+            let mut base = vec!["config"];
+            if scope == Scope::Global {
+                base.push("--global");
+            }
+            if !add {
+                let mut unset = base.clone();
+                unset.extend(["--unset-all", &git_key]);
+                // Fails harmlessly when the key isn't set
+                let _ = git::output(store.repo(), &unset);
+            }
+            for value in values {
+                let mut args = base.clone();
+                args.extend(["--add", &git_key, value]);
+                git::run(store.repo(), &args)?;
+            }
+            Ok(())
+        }
+        Scope::Shared => set_shared(store, key, values, add),
+    }
+}
+
+fn set_shared(store: &RecordStore, key: &Key, values: &[String], add: bool) -> Result<()> {
+    if key.name == "branch" {
+        return Err(anyhow!(
+            "branch can't be shared: {} lives on the branch it would name",
+            CONFIG_FILE
+        ));
+    }
+    let content = store.backend().read(CONFIG_FILE)?.unwrap_or_default();
+    let mut root: toml::Table = toml::from_str(&content)
+        .map_err(|e| anyhow!("Invalid {}: {}", CONFIG_FILE, e.message()))?;
+
+    let mut path: Vec<&str> = key.name.split('.').collect();
+    let name = path.pop().unwrap_or(key.name);
+    let mut table = &mut root;
+    for section in path {
+        table = table
+            .entry(section)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("{} in {} is not a table", section, CONFIG_FILE))?;
+    }
+
+    let typed = |v: &String| match v.parse::<i64>() {
+        Ok(n) => toml::Value::Integer(n),
+        Err(_) => toml::Value::String(v.clone()),
+    };
+    let message = if values.is_empty() {
+        table.remove(name);
+        format!("Config: unset {}", key.name)
+    } else if key.list {
+        let mut items = match (add, table.remove(name)) {
+            (true, Some(toml::Value::Array(items))) => items,
+            (true, Some(value)) => vec![value],
+            _ => Vec::new(),
+        };
+        items.extend(values.iter().map(typed));
+        table.insert(name.to_string(), toml::Value::Array(items));
+        format!("Config: set {}", key.name)
+    } else {
+        table.insert(name.to_string(), typed(&values[0]));
+        format!("Config: set {}", key.name)
+    };
+
+    let content = toml::to_string_pretty(&root)?;
+    store.commit(&[(CONFIG_FILE.to_string(), Some(content))], &message)
+}
@@ -0,0 +1,126 @@
+//! Reasoning coverage of a revision range (`arf coverage`): which commits
+//! have records, overall and per author.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::git::{self, CommitInfo, Person};
+use crate::store::RecordStore;
+
+/// A commit in the range and how many records explain it.
+#[derive(Debug, Clone)]
+pub struct CommitCoverage {
+    pub commit: CommitInfo,
+    /// Valid records attached to the commit, not counting retracted ones.
+    pub records: usize,
+}
+
+impl CommitCoverage {
+    pub fn is_covered(&self) -> bool {
+        self.records > 0
+    }
+}
+
+/// Coverage of one author's commits.
+#[derive(Debug, Clone)]
+pub struct AuthorCoverage {
+    pub author: Person,
+    pub commits: usize,
+    pub covered: usize,
+}
+
+impl AuthorCoverage {
+    pub fn percent(&self) -> f64 {
+        percent(self.covered, self.commits)
+    }
+}
+
+/// Coverage of a revision range, newest commit first.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    pub commits: Vec<CommitCoverage>,
+}
+
+impl Coverage {
+    /// Coverage of the commits `git log <args>` lists, e.g. `["main..HEAD"]`
+    /// or `["--no-merges", "main..HEAD"]`.
+    pub fn compute(store: &RecordStore, args: &[&str]) -> Result<Self> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for stored in store.list()? {
+            if stored.record.retracted.is_some() || stored.record.validate().is_err() {
+                continue;
+            }
+            if let Some(commit) = stored.record.commit {
+                *counts.entry(commit).or_default() += 1;
+            }
+        }
+        // Records from before full SHAs may name a commit by prefix
+        let short: Vec<(&String, &usize)> = counts
+            .iter()
+            .filter(|(commit, _)| !git::is_full_sha(commit))
+            .collect();
+
+        let commits = git::commits(store.repo(), args)?
+            .into_iter()
+            .map(|commit| {
+                let records = counts.get(&commit.sha).copied().unwrap_or(0)
+                    + short
+                        .iter()
+                        .filter(|(prefix, _)| commit.sha.starts_with(prefix.as_str()))
+                        .map(|(_, n)| **n)
+                        .sum::<usize>();
+                CommitCoverage { commit, records }
+            })
+            .collect();
+
+        Ok(Self { commits })
+    }
+
+    pub fn covered(&self) -> usize {
+        self.commits.iter().filter(|c| c.is_covered()).count()
+    }
+
+    /// Share of commits with reasoning, 0 to 100. An empty range counts as
+    /// fully covered: there is nothing missing.
+    pub fn percent(&self) -> f64 {
+        percent(self.covered(), self.commits.len())
+    }
+
+    /// Commits without reasoning, newest first.
+    pub fn missing(&self) -> impl Iterator<Item = &CommitInfo> {
+        self.commits
+            .iter()
+            .filter(|c| !c.is_covered())
+            .map(|c| &c.commit)
+    }
+
+    /// Coverage per author (by email), most commits first.
+    pub fn by_author(&self) -> Vec<AuthorCoverage> {
+        let mut authors: BTreeMap<String, AuthorCoverage> = BTreeMap::new();
+        for c in &self.commits {
+            let author = authors
+                .entry(c.commit.author.email.to_lowercase())
+                .or_insert_with(|| AuthorCoverage {
+                    author: c.commit.author.clone(),
+                    commits: 0,
+                    covered: 0,
+                });
+            author.commits += 1;
+            if c.is_covered() {
+                author.covered += 1;
+            }
+        }
+
+        let mut authors: Vec<AuthorCoverage> = authors.into_values().collect();
+        authors.sort_by_key(|a| std::cmp::Reverse(a.commits));
+        authors
+    }
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
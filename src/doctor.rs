@@ -0,0 +1,332 @@
+//! Consistency checks for stored records (`arf doctor`).
+
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::backend::Change;
+use crate::git;
+use crate::record::ArfRecord;
+use crate::store::{RecordStore, Storage};
+
+/// A problem found by [`check`].
+#[derive(Debug, Clone)]
+pub enum Issue {
+    /// A record file or note that isn't a valid ARF record.
+    Unparseable { path: String, error: String },
+    /// A record whose `commit` field names a different commit than the
+    /// directory it's stored in.
+    CommitMismatch {
+        path: String,
+        field: Option<String>,
+        dir: String,
+    },
+    /// A record directory keyed by a short SHA (see `arf migrate`).
+    ShortSha { dir: String },
+    /// Records for a commit that doesn't exist in this repository. `note`
+    /// is set when they're in a note rather than a `records/` directory.
+    MissingCommit { dir: String, note: bool },
+    /// Records for a commit no ref can reach anymore, e.g. after a rebase
+    /// without the post-rewrite hook.
+    Unreachable { dir: String, note: bool },
+    /// A note on an unreachable commit whose records were all copied to a
+    /// rewritten commit, as git does for amends and rebases.
+    CopiedNote { dir: String },
+    /// A file under `records/` that isn't a `.toml` record in a commit
+    /// directory.
+    UnexpectedFile { path: String },
+    /// Changes in the `.arf/` worktree that were never committed.
+    Uncommitted { paths: Vec<String> },
+}
+
+impl Issue {
+    /// Whether `arf doctor --fix` can repair this without guessing.
+    pub fn is_fixable(&self) -> bool {
+        matches!(
+            self,
+            Issue::CommitMismatch { .. }
+                | Issue::ShortSha { .. }
+                | Issue::CopiedNote { .. }
+                | Issue::Uncommitted { .. }
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Unparseable { path, error } => {
+                write!(f, "{}: failed to parse: {}", path, error)
+            }
+            Issue::CommitMismatch { path, field, dir } => write!(
+                f,
+                "{}: commit field is {} but record is stored under {}",
+                path,
+                field.as_deref().unwrap_or("missing"),
+                git::short_sha(dir)
+            ),
+            Issue::ShortSha { dir } => {
+                write!(f, "records/{}: keyed by short SHA (run 'arf migrate')", dir)
+            }
+            Issue::MissingCommit { dir, note } => {
+                write!(f, "{}: commit does not exist", location(dir, *note))
+            }
+            Issue::Unreachable { dir, note } => write!(
+                f,
+                "{}: commit is not reachable from any ref",
+                location(dir, *note)
+            ),
+            Issue::CopiedNote { dir } => write!(
+                f,
+                "note on {}: left behind by a rewrite; its records are on the new commit",
+                git::short_sha(dir)
+            ),
+            Issue::UnexpectedFile { path } if path.matches('/').count() < 2 => {
+                write!(f, "{}: not inside a commit directory", path)
+            }
+            Issue::UnexpectedFile { path } => write!(f, "{}: not a .toml record", path),
+            Issue::Uncommitted { paths } => {
+                write!(f, "uncommitted changes in .arf/: {}", paths.join(", "))
+            }
+        }
+    }
+}
+
+/// Walk every record and report problems.
+pub fn check(store: &RecordStore) -> Result<Vec<Issue>> {
+    let repo = store.repo();
+    let reachable = store.reachable_commits()?;
+    let mut issues = Vec::new();
+
+    let check_commit = |dir: &str, note: bool, issues: &mut Vec<Issue>| {
+        if !git::is_full_sha(dir) {
+            issues.push(Issue::ShortSha {
+                dir: dir.to_string(),
+            });
+        } else if git::ref_sha(repo, dir).is_none() {
+            issues.push(Issue::MissingCommit {
+                dir: dir.to_string(),
+                note,
+            });
+        } else if !reachable.contains(dir) {
+            issues.push(Issue::Unreachable {
+                dir: dir.to_string(),
+                note,
+            });
+        }
+    };
+
+    if store.storage() == Storage::Notes {
+        let notes = store.notes().read_all()?;
+        let ids = |content: &str| -> Vec<String> {
+            ArfRecord::from_toml_stream(content)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|r| r.id)
+                .collect()
+        };
+        // What reachable commits carry, to spot the originals git leaves
+        // behind when it copies notes on a rewrite
+        let live: HashSet<String> = notes
+            .iter()
+            .filter(|(commit, _)| reachable.contains(commit))
+            .flat_map(|(_, content)| ids(content))
+            .collect();
+
+        for (commit, content) in &notes {
+            let records = match ArfRecord::from_toml_stream(content) {
+                Ok(records) => records,
+                Err(e) => {
+                    issues.push(Issue::Unparseable {
+                        path: format!("note on {}", git::short_sha(commit)),
+                        error: describe(&e, content),
+                    });
+                    Vec::new()
+                }
+            };
+            let copied = !records.is_empty()
+                && records
+                    .iter()
+                    .all(|r| r.id.as_ref().is_some_and(|id| live.contains(id)));
+            if copied && !reachable.contains(commit) && git::ref_sha(repo, commit).is_some() {
+                issues.push(Issue::CopiedNote {
+                    dir: commit.clone(),
+                });
+            } else {
+                check_commit(commit, true, &mut issues);
+            }
+        }
+        return Ok(issues);
+    }
+
+    let backend = store.backend();
+    let mut files: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for (path, content) in backend.read_all("records")? {
+        match path.split('/').nth(1) {
+            Some(dir) if path.matches('/').count() >= 2 => {
+                files
+                    .entry(dir.to_string())
+                    .or_default()
+                    .push((path.clone(), content));
+            }
+            _ => issues.push(Issue::UnexpectedFile { path }),
+        }
+    }
+
+    for (dir, dir_files) in files {
+        check_commit(&dir, false, &mut issues);
+        let dir_exists = git::is_full_sha(&dir) && git::ref_sha(repo, &dir).is_some();
+
+        for (path, content) in dir_files {
+            if !path.ends_with(".toml") {
+                issues.push(Issue::UnexpectedFile { path });
+                continue;
+            }
+
+            let records = match ArfRecord::from_toml_file(&content) {
+                Ok(records) => records,
+                Err(e) => {
+                    issues.push(Issue::Unparseable {
+                        error: describe(&e, &content),
+                        path,
+                    });
+                    continue;
+                }
+            };
+
+            // Short-SHA directories are reported (and fixed) as a whole, and
+            // a field can't be "fixed" to name a commit that doesn't exist
+            if !dir_exists {
+                continue;
+            }
+
+            let mismatch = records.into_iter().find(|record| {
+                !record.commit.as_deref().is_some_and(|c| {
+                    c == dir || git::resolve_commit(repo, c).is_ok_and(|s| s == dir)
+                })
+            });
+            if let Some(record) = mismatch {
+                issues.push(Issue::CommitMismatch {
+                    path,
+                    field: record.commit,
+                    dir: dir.clone(),
+                });
+            }
+        }
+    }
+
+    if let Some(worktree) = store.worktree() {
+        let status = git::worktree_output(&worktree, &["status", "--porcelain"])?;
+        let status = String::from_utf8_lossy(&status.stdout);
+        let paths: Vec<String> = status
+            .lines()
+            .filter_map(|l| l.get(3..))
+            .map(|p| p.to_string())
+            .collect();
+        if !paths.is_empty() {
+            issues.push(Issue::Uncommitted { paths });
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Repair the fixable issues, returning the ones that were fixed.
+///
+/// Mismatched `commit` fields are set to the directory the record is stored
+/// under, short-SHA directories are migrated, leftover worktree changes are
+/// committed, and notes left behind by a rewrite are removed.
+pub fn fix(store: &RecordStore, issues: &[Issue]) -> Result<Vec<Issue>> {
+    let mut fixed = Vec::new();
+
+    // Commit stray worktree changes first so later commits don't sweep them up
+    if let Some(issue @ Issue::Uncommitted { .. }) = issues
+        .iter()
+        .find(|i| matches!(i, Issue::Uncommitted { .. }))
+    {
+        store.commit(&[], "Commit leftover ARF changes")?;
+        fixed.push(issue.clone());
+    }
+
+    let mut changes: Vec<Change> = Vec::new();
+    for issue in issues {
+        if let Issue::CommitMismatch { path, dir, .. } = issue {
+            let Some(content) = store.backend().read(path)? else {
+                continue;
+            };
+            let mut records = ArfRecord::from_toml_file(&content)?;
+            for record in &mut records {
+                record.commit = Some(dir.clone());
+            }
+            let content = ArfRecord::to_toml_file(&records)?;
+            changes.push((path.clone(), Some(content)));
+            fixed.push(issue.clone());
+        }
+    }
+    if !changes.is_empty() {
+        store.commit(&changes, "Fix record commit fields")?;
+    }
+
+    for issue in issues {
+        if let Issue::CopiedNote { dir } = issue {
+            store.notes().write(dir, "")?;
+            fixed.push(issue.clone());
+        }
+    }
+
+    if issues.iter().any(|i| matches!(i, Issue::ShortSha { .. })) {
+        let migration = store.migrate(false)?;
+        let dir_of = |path: &String| path.split('/').nth(1).map(|d| d.to_string());
+        let moved: HashSet<String> = migration
+            .moved
+            .iter()
+            .filter_map(|(p, _)| dir_of(p))
+            .collect();
+        let skipped: HashSet<String> = migration
+            .skipped
+            .iter()
+            .filter_map(|(p, _)| dir_of(p))
+            .collect();
+        fixed.extend(
+            issues
+                .iter()
+                .filter(|i| match i {
+                    Issue::ShortSha { dir } => moved.contains(dir) && !skipped.contains(dir),
+                    _ => false,
+                })
+                .cloned(),
+        );
+    }
+
+    Ok(fixed)
+}
+
+/// Where an issue's records live: `note on <sha>` or `records/<dir>`.
+fn location(dir: &str, note: bool) -> String {
+    if note {
+        format!("note on {}", git::short_sha(dir))
+    } else {
+        format!("records/{}", dir)
+    }
+}
+
+/// One-line summary of a TOML error: the message and the line it's on.
+fn describe(error: &toml::de::Error, content: &str) -> String {
+    let message = error
+        .message()
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+    match error.span() {
+        Some(span) => {
+            let line = content[..span.start.min(content.len())]
+                .lines()
+                .count()
+                .max(1);
+            format!("{} (line {})", message, line)
+        }
+        None => message,
+    }
+}
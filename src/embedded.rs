@@ -0,0 +1,285 @@
+//! Records embedded in commit messages.
+//!
+//! SPEC.md allows reasoning to travel inside the commit itself. Two forms are
+//! recognised in a message body:
+//!
+//! ````text
+//! ```arf
+//! what = "Add retry logic"
+//! why = "Transient API failures"
+//! ```
+//! ````
+//!
+//! (a ```` ```toml ```` fence works too, as long as it has `what` and `why`),
+//! or trailers, in the message's final paragraph as git expects them:
+//!
+//! ```text
+//! What: Add retry logic
+//! Why: Transient API failures
+//! How: Exponential backoff in fetch()
+//! ```
+//!
+//! Embedded records have no stored ID, so one is derived from the commit and
+//! the record's position in the message: reading the same commit always
+//! gives the same IDs.
+
+use chrono::DateTime;
+
+use crate::id;
+use crate::record::{ArfRecord, InputFormat};
+
+/// Trailer keys and the record fields they fill.
+const TRAILERS: &[(&str, &str)] = &[
+    ("what", "what"),
+    ("why", "why"),
+    ("how", "how"),
+    ("backup", "backup"),
+    ("agent", "agent"),
+];
+
+/// Every record embedded in `message`, the message of `commit`. Records
+/// without a timestamp get `timestamp` (typically the commit's author date);
+/// the agent is only what the message names, never `ARF_AGENT`.
+pub fn extract(commit: &str, message: &str, timestamp: &str) -> Vec<ArfRecord> {
+    let mut records = Vec::new();
+
+    for block in fenced_blocks(message) {
+        let mut table = match toml::from_str::<toml::Table>(&block) {
+            Ok(table) => table,
+            Err(_) => continue,
+        };
+        // A block may hold one record or a `[[record]]` stream
+        let entries = match table.remove("record") {
+            Some(toml::Value::Array(entries)) => entries
+                .into_iter()
+                .filter_map(|e| e.as_table().cloned())
+                .collect(),
+            _ => vec![table],
+        };
+        for mut entry in entries {
+            entry.entry("timestamp").or_insert_with(|| timestamp.into());
+            let has_agent = entry.contains_key("agent");
+            let given_id = entry.get("id").and_then(|v| v.as_str()).map(String::from);
+            let Ok(content) = toml::to_string(&entry) else {
+                continue;
+            };
+            if let Ok(mut record) = ArfRecord::from_input(&content, InputFormat::Toml) {
+                if !has_agent {
+                    record.agent = None;
+                }
+                record.id = given_id.or_else(|| Some(derived_id(commit, records.len(), timestamp)));
+                records.push(record);
+            }
+        }
+    }
+
+    if let Some(mut record) = from_trailers(message, timestamp) {
+        record.id = Some(derived_id(commit, records.len(), timestamp));
+        records.push(record);
+    }
+
+    records
+}
+
+/// The ID of the `index`th record embedded in `commit`: the commit time, then
+/// 72 bits of the SHA and the index.
+fn derived_id(commit: &str, index: usize, timestamp: &str) -> String {
+    let millis = DateTime::parse_from_rfc3339(timestamp)
+        .map_or(0, |time| time.timestamp_millis().max(0) as u64);
+    let sha = commit
+        .get(..18)
+        .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+        .unwrap_or(0);
+    id::encode(millis, (sha << 8) | (index as u128 & 0xff))
+}
+
+/// Contents of ```` ```arf ```` and ```` ```toml ```` fences.
+fn fenced_blocks(message: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+
+    for line in message.lines() {
+        let trimmed = line.trim();
+        match current {
+            Some(ref mut block) if trimmed.starts_with("```") => {
+                blocks.push(std::mem::take(block));
+                current = None;
+            }
+            Some(ref mut block) => {
+                block.push_str(line);
+                block.push('\n');
+            }
+            None => {
+                if let Some(lang) = trimmed.strip_prefix("```") {
+                    if matches!(lang.trim(), "arf" | "toml") {
+                        current = Some(String::new());
+                    }
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// A record from `What:`/`Why:` trailers, if both are present. Indented
+/// lines continue the previous trailer, as with git trailers.
+fn from_trailers(message: &str, timestamp: &str) -> Option<ArfRecord> {
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    // Whether the previous line was one of our trailers (or continued one)
+    let mut continuing = false;
+
+    for line in trailer_block(message) {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut().filter(|_| continuing) {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+
+        continuing = false;
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        if let Some((_, field)) = TRAILERS.iter().find(|(k, _)| *k == key) {
+            fields.push((field, value.trim().to_string()));
+            continuing = true;
+        }
+    }
+
+    let get = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, value)| *field == name && !value.is_empty())
+            .map(|(_, value)| value.clone())
+    };
+    let mut record = ArfRecord::new(get("what")?, get("why")?);
+    record.how = get("how");
+    record.backup = get("backup");
+    record.agent = get("agent");
+    record.timestamp = timestamp.to_string();
+    Some(record)
+}
+
+/// The message's trailer block: its last paragraph, when that isn't the
+/// subject and every line is a `Key: value` trailer or continues one. Fenced
+/// blocks count as ordinary text, blank lines and all.
+fn trailer_block(message: &str) -> Vec<&str> {
+    let mut paragraphs: Vec<Vec<&str>> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    for line in message.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if line.trim().is_empty() && !in_fence {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        current.push(line);
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+
+    let is_trailer = |line: &&str| {
+        line.split_once(':').is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    };
+    let continues = |line: &&str| line.starts_with([' ', '\t']);
+    match paragraphs.pop() {
+        Some(block)
+            if !paragraphs.is_empty()
+                && block.first().is_some_and(is_trailer)
+                && block.iter().all(|l| is_trailer(l) || continues(l)) =>
+        {
+            block
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "4aaf1eb84fe7784c91eb8079720abb96c79087b4";
+    const TIME: &str = "2026-03-01T12:00:00+00:00";
+
+    fn whats(message: &str) -> Vec<String> {
+        extract(SHA, message, TIME)
+            .into_iter()
+            .map(|r| r.what)
+            .collect()
+    }
+
+    #[test]
+    fn fenced_blocks() {
+        let message = "Add retries\n\n```arf\nwhat = \"Add retry logic\"\nwhy = \"Flaky API\"\n```\n\n```toml\n[[record]]\nwhat = \"Cap the backoff\"\nwhy = \"Waits grew\"\n```\n\n```rust\nlet what = 1;\n```\n";
+        assert_eq!(whats(message), ["Add retry logic", "Cap the backoff"]);
+
+        let record = &extract(SHA, message, TIME)[0];
+        assert_eq!(record.timestamp, TIME);
+        assert_eq!(record.agent, None);
+    }
+
+    #[test]
+    fn fenced_blocks_need_what_and_why() {
+        assert!(whats("Subject\n\n```toml\nname = \"arf\"\n```\n").is_empty());
+    }
+
+    #[test]
+    fn trailers_in_the_final_paragraph() {
+        let message = "Add retries\n\nSome context.\n\nWhat: Add retry logic\nWhy: Transient API\n  failures\nHow: Backoff in fetch()\nSigned-off-by: Dev <dev@example.com>\n";
+        let records = extract(SHA, message, TIME);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].what, "Add retry logic");
+        assert_eq!(records[0].why, "Transient API failures");
+        assert_eq!(records[0].how.as_deref(), Some("Backoff in fetch()"));
+    }
+
+    #[test]
+    fn trailer_lookalikes_in_prose_are_ignored() {
+        // Not the last paragraph
+        let message =
+            "Tidy docs\n\nWhat: changed is the intro\nWhy: it was stale\n\nNo trailers here.\n";
+        assert!(whats(message).is_empty());
+        // Last paragraph, but not all trailers
+        let message = "Tidy docs\n\nWhat: the intro\nWhy: it was stale\nand more words.\n";
+        assert!(whats(message).is_empty());
+        // The subject is never a trailer
+        assert!(whats("What: a subject\nWhy: no body\n").is_empty());
+        // Inside a fence at the end
+        let message = "Tidy docs\n\n```\nWhat: example\nWhy: example\n```\n";
+        assert!(whats(message).is_empty());
+    }
+
+    #[test]
+    fn ids_are_stable_per_commit_and_position() {
+        let message = "Subject\n\n```arf\nwhat = \"One\"\nwhy = \"A\"\n```\n\nWhat: Two\nWhy: B\n";
+        let first = extract(SHA, message, TIME);
+        let again = extract(SHA, message, TIME);
+        let ids = |records: &[ArfRecord]| -> Vec<String> {
+            records.iter().map(|r| r.id.clone().unwrap()).collect()
+        };
+        assert_eq!(first.len(), 2);
+        assert_eq!(ids(&first), ids(&again));
+        assert_ne!(ids(&first)[0], ids(&first)[1]);
+        assert!(ids(&first).iter().all(|id| id::is_valid(id)));
+
+        let other = extract(&SHA.replace('4', "5"), message, TIME);
+        assert_ne!(ids(&first), ids(&other));
+    }
+
+    #[test]
+    fn ids_given_in_a_block_are_kept() {
+        let message = "Subject\n\n```arf\nid = \"01M53SY77BE8MVQ5DPM0ZAECTW\"\nwhat = \"One\"\nwhy = \"A\"\n```\n";
+        let records = extract(SHA, message, TIME);
+        assert_eq!(records[0].id.as_deref(), Some("01M53SY77BE8MVQ5DPM0ZAECTW"));
+    }
+}
@@ -0,0 +1,384 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A commit as listed by `git log`.
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub sha: String,
+    pub short_sha: String,
+    pub subject: String,
+}
+
+/// A commit with its author and committer, for machine-readable output.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitInfo {
+    pub sha: String,
+    pub short_sha: String,
+    pub parents: Vec<String>,
+    pub author: Person,
+    /// Author date, ISO 8601.
+    pub author_date: String,
+    pub committer: Person,
+    /// Committer date, ISO 8601.
+    pub committer_date: String,
+    pub subject: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+}
+
+/// Lines added and removed in one file of a commit.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStat {
+    pub path: String,
+    /// `None` for binary files.
+    pub insertions: Option<u64>,
+    pub deletions: Option<u64>,
+}
+
+/// Run git in `dir` and return the raw output, whether or not it succeeded.
+pub fn output(dir: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new("git").args(args).current_dir(dir).output()?)
+}
+
+/// Run git in a different worktree than the one arf was started in.
+///
+/// Git exports `GIT_DIR`, `GIT_WORK_TREE`, and `GIT_INDEX_FILE` to hooks.
+/// They describe the main checkout, so they must not leak into commands run
+/// in the `.arf/` worktree.
+pub fn worktree_output(dir: &Path, args: &[&str]) -> Result<Output> {
+    Ok(Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE")
+        .env_remove("GIT_INDEX_FILE")
+        .output()?)
+}
+
+/// Run git in `dir` and return trimmed stdout, failing with git's stderr.
+pub fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = output(dir, args)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run git in `dir` with `input` on stdin and return raw stdout.
+pub fn pipe(dir: &Path, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write from a separate thread so a large input can't deadlock against
+    // git filling its stdout pipe.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| anyhow!("git {} stdin writer panicked", args.first().unwrap_or(&"")))??;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Run git in `dir` with `input` on stdin and return trimmed stdout.
+pub fn run_with_input(dir: &Path, args: &[&str], input: &[u8]) -> Result<String> {
+    let stdout = pipe(dir, args, input)?;
+    Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+}
+
+/// Contents of several blobs, read with a single `git cat-file --batch`.
+pub fn read_blobs(dir: &Path, oids: &[&str]) -> Result<Vec<String>> {
+    if oids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let input: String = oids.iter().map(|oid| format!("{}\n", oid)).collect();
+    let raw = pipe(dir, &["cat-file", "--batch"], input.as_bytes())?;
+
+    // Each object is "<oid> <type> <size>\n<content>\n"
+    let mut contents = Vec::new();
+    let mut pos = 0;
+    for _ in oids {
+        let header_end = raw[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| anyhow!("Truncated cat-file output"))?
+            + pos;
+        let header = String::from_utf8_lossy(&raw[pos..header_end]);
+        let size: usize = header
+            .rsplit(' ')
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| anyhow!("Bad cat-file header: {}", header))?;
+        let start = header_end + 1;
+        let end = start + size;
+        if end > raw.len() {
+            return Err(anyhow!("Truncated cat-file output"));
+        }
+        contents.push(String::from_utf8_lossy(&raw[start..end]).to_string());
+        pos = end + 1;
+    }
+
+    Ok(contents)
+}
+
+/// Resolve a ref to its commit SHA, or `None` if it doesn't exist.
+pub fn ref_sha(dir: &Path, name: &str) -> Option<String> {
+    let spec = format!("{}^{{commit}}", name);
+    output(dir, &["rev-parse", "--verify", "--quiet", &spec])
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+}
+
+/// Whether the repository has no working tree.
+pub fn is_bare(dir: &Path) -> bool {
+    run(dir, &["rev-parse", "--is-bare-repository"]).is_ok_and(|s| s == "true")
+}
+
+/// Whether `dir` is inside a git repository.
+pub fn is_repo(dir: &Path) -> bool {
+    output(dir, &["rev-parse", "--git-dir"]).is_ok_and(|o| o.status.success())
+}
+
+/// Root of the worktree containing `dir`, or `None` in a bare repository or
+/// outside git.
+pub fn toplevel(dir: &Path) -> Option<PathBuf> {
+    run(dir, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The git directory shared by every worktree of the repository: the main
+/// checkout's `.git`, even when `dir` is in a linked worktree.
+pub fn common_dir(dir: &Path) -> Result<PathBuf> {
+    let path = PathBuf::from(run(dir, &["rev-parse", "--git-common-dir"])?);
+    Ok(if path.is_absolute() {
+        path
+    } else {
+        dir.join(path)
+    })
+}
+
+/// A worktree of the repository, as listed by `git worktree list`.
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    pub path: PathBuf,
+    /// Checked-out branch ref, if not detached.
+    pub branch: Option<String>,
+    pub bare: bool,
+}
+
+/// Every worktree of the repository, the main one first. Worktrees whose
+/// directory is gone are left out.
+pub fn worktrees(dir: &Path) -> Vec<Worktree> {
+    let Ok(out) = run(dir, &["worktree", "list", "--porcelain"]) else {
+        return Vec::new();
+    };
+    out.split("\n\n")
+        .filter_map(|entry| {
+            let mut worktree = Worktree {
+                path: PathBuf::from(entry.lines().next()?.strip_prefix("worktree ")?),
+                branch: None,
+                bare: false,
+            };
+            for line in entry.lines() {
+                if let Some(branch) = line.strip_prefix("branch ") {
+                    worktree.branch = Some(branch.to_string());
+                }
+                worktree.bare |= line == "bare";
+            }
+            worktree.path.exists().then_some(worktree)
+        })
+        .collect()
+}
+
+/// The worktree that has `branch_ref` (e.g. `refs/heads/arf`) checked out.
+pub fn worktree_of(dir: &Path, branch_ref: &str) -> Option<PathBuf> {
+    worktrees(dir)
+        .into_iter()
+        .find(|w| w.branch.as_deref() == Some(branch_ref))
+        .map(|w| w.path)
+}
+
+/// Resolve a revision (SHA prefix, branch, `HEAD~2`, ...) to a full commit SHA.
+///
+/// A SHA prefix shared by several commits is an error listing the candidates,
+/// rather than a guess.
+pub fn resolve_commit(dir: &Path, rev: &str) -> Result<String> {
+    if let Some(sha) = ref_sha(dir, rev) {
+        return Ok(sha);
+    }
+
+    let candidates = commits_with_prefix(dir, rev)?;
+    if candidates.len() > 1 {
+        return Err(anyhow!(
+            "Ambiguous commit prefix '{}' matches:\n  {}",
+            rev,
+            candidates.join("\n  ")
+        ));
+    }
+
+    Err(anyhow!("Commit not found: {}", rev))
+}
+
+/// All commits whose SHA starts with `prefix`.
+pub fn commits_with_prefix(dir: &Path, prefix: &str) -> Result<Vec<String>> {
+    if prefix.len() < 4 || !is_hex(prefix) {
+        return Ok(Vec::new());
+    }
+
+    let output = output(dir, &["rev-parse", &format!("--disambiguate={}", prefix)])?;
+    let objects = String::from_utf8_lossy(&output.stdout).to_string();
+    if objects.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let types = pipe(
+        dir,
+        &["cat-file", "--batch-check=%(objectname) %(objecttype)"],
+        objects.as_bytes(),
+    )?;
+    Ok(String::from_utf8_lossy(&types)
+        .lines()
+        .filter_map(|l| l.strip_suffix(" commit"))
+        .map(|sha| sha.to_string())
+        .collect())
+}
+
+/// Whether `s` is a complete object ID (SHA-1 or SHA-256).
+pub fn is_full_sha(s: &str) -> bool {
+    (s.len() == 40 || s.len() == 64) && is_hex(s)
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Full SHA of HEAD.
+pub fn head(dir: &Path) -> Result<String> {
+    resolve_commit(dir, "HEAD").map_err(|_| anyhow!("Failed to get HEAD commit"))
+}
+
+/// The most recent `limit` commits reachable from HEAD.
+pub fn log(dir: &Path, limit: usize) -> Result<Vec<CommitSummary>> {
+    let output = output(
+        dir,
+        &[
+            "log",
+            "--format=%H %h %s",
+            "--no-decorate",
+            &format!("-{}", limit),
+        ],
+    )?;
+
+    if !output.status.success() {
+        return Err(anyhow!("Failed to get git log"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            Some(CommitSummary {
+                sha: parts.next()?.to_string(),
+                short_sha: parts.next()?.to_string(),
+                subject: parts.next().unwrap_or("").to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Commits listed by `git log <args>`, with full metadata.
+pub fn commits(dir: &Path, args: &[&str]) -> Result<Vec<CommitInfo>> {
+    // Unit separator between fields, record separator between commits
+    let format = "--format=%H%x1f%h%x1f%P%x1f%an%x1f%ae%x1f%aI%x1f%cn%x1f%ce%x1f%cI%x1f%s%x1e";
+    let mut log_args = vec!["log", format, "--no-decorate"];
+    log_args.extend_from_slice(args);
+    let out = run(dir, &log_args)?;
+
+    Ok(out
+        .split('\x1e')
+        .filter_map(|entry| {
+            let mut f = entry.trim_start_matches('\n').split('\x1f');
+            Some(CommitInfo {
+                sha: f.next().filter(|s| !s.is_empty())?.to_string(),
+                short_sha: f.next()?.to_string(),
+                parents: f.next()?.split_whitespace().map(String::from).collect(),
+                author: Person {
+                    name: f.next()?.to_string(),
+                    email: f.next()?.to_string(),
+                },
+                author_date: f.next()?.to_string(),
+                committer: Person {
+                    name: f.next()?.to_string(),
+                    email: f.next()?.to_string(),
+                },
+                committer_date: f.next()?.to_string(),
+                subject: f.next()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Full message (subject and body) of a commit.
+pub fn message(dir: &Path, sha: &str) -> Result<String> {
+    run(dir, &["log", "-1", "--format=%B", sha])
+}
+
+/// Metadata for a single commit.
+pub fn commit_info(dir: &Path, sha: &str) -> Result<CommitInfo> {
+    commits(dir, &["-1", sha])?
+        .pop()
+        .ok_or_else(|| anyhow!("Commit not found: {}", sha))
+}
+
+/// Per-file line counts for a commit (`git show --numstat`).
+pub fn file_stats(dir: &Path, sha: &str) -> Result<Vec<FileStat>> {
+    let out = run(dir, &["show", "--numstat", "--format=", sha])?;
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let insertions = parts.next()?.parse().ok();
+            let deletions = parts.next()?.parse().ok();
+            Some(FileStat {
+                path: parts.next()?.to_string(),
+                insertions,
+                deletions,
+            })
+        })
+        .collect())
+}
+
+/// First 8 characters of a SHA, for display.
+pub fn short_sha(sha: &str) -> &str {
+    &sha[..8.min(sha.len())]
+}
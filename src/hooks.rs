@@ -0,0 +1,226 @@
+//! Git hooks that keep records attached to the right commits.
+
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::git;
+
+/// First line after the shebang in every hook arf writes, so it can
+/// recognise (and replace or remove) its own hooks.
+const MARKER: &str = "# Installed by arf";
+
+/// A hook arf knows how to install.
+#[derive(Debug, Clone, Copy)]
+pub struct Hook {
+    /// Git's name for the hook, e.g. `post-rewrite`.
+    pub name: &'static str,
+    /// What it does, for `arf hooks install` output.
+    pub description: &'static str,
+    /// Installed by a plain `arf hooks install`; others must be named.
+    pub default: bool,
+    body: &'static str,
+}
+
+/// Every hook arf can install.
+pub const HOOKS: &[Hook] = &[
+    Hook {
+        name: "post-rewrite",
+        description: "re-attach records after amend and rebase",
+        default: true,
+        body: r#"# git passes "<old-sha> <new-sha>" pairs on stdin
+exec arf rewrite "$1"
+"#,
+    },
+    Hook {
+        name: "post-commit",
+        description: "attach pending records to the new commit",
+        default: true,
+        body: r#"# Leave pending records alone while a rebase replays commits
+[ -d "$(git rev-parse --git-path rebase-merge)" ] && exit 0
+[ -d "$(git rev-parse --git-path rebase-apply)" ] && exit 0
+exec arf bind
+"#,
+    },
+    Hook {
+        name: "pre-push",
+        description: "refuse to push commits that need reasoning and lack it",
+        default: false,
+        body: r#"# git passes "<local-ref> <local-sha> <remote-ref> <remote-sha>" lines on stdin
+zero=$(git hash-object --stdin </dev/null | tr '0-9a-f' '0')
+branch=${ARF_BRANCH:-$(git config --get arf.branch || echo arf)}
+status=0
+while read -r local_ref local_sha remote_ref remote_sha; do
+    # Deletions, and ARF's own refs, have nothing to check
+    [ "$local_sha" = "$zero" ] && continue
+    case "$local_ref" in "refs/heads/$branch" | refs/notes/*) continue ;; esac
+
+    if [ "$remote_sha" != "$zero" ] && git cat-file -e "$remote_sha^{commit}" 2>/dev/null; then
+        range="$remote_sha..$local_sha"
+    else
+        range="$local_sha --not --remotes=$1"
+    fi
+    arf check "$range" || status=1
+done
+exit $status
+"#,
+    },
+];
+
+/// What happened to a hook during install or uninstall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStatus {
+    Installed,
+    Updated,
+    Removed,
+    /// A hook not written by arf is in the way.
+    Skipped,
+    /// Nothing to remove.
+    Missing,
+}
+
+/// Directory git runs hooks from, honouring `core.hooksPath`.
+pub fn hooks_dir(repo: &Path) -> Result<PathBuf> {
+    let dir = git::run(repo, &["rev-parse", "--git-path", "hooks"])?;
+    let dir = PathBuf::from(dir);
+    Ok(if dir.is_absolute() {
+        dir
+    } else {
+        repo.join(dir)
+    })
+}
+
+/// Whether the hook called `name` is installed by arf.
+pub fn is_installed(repo: &Path, name: &str) -> bool {
+    hooks_dir(repo).is_ok_and(|dir| is_ours(&dir.join(name)))
+}
+
+fn script(hook: &Hook) -> String {
+    format!("#!/bin/sh\n{}\n{}", MARKER, hook.body)
+}
+
+fn is_ours(path: &Path) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| content.contains(MARKER))
+}
+
+/// Install the hooks called `names`, or every default hook in [`HOOKS`] if
+/// `names` is empty. Existing hooks not written by arf are left alone unless
+/// `force` is set.
+pub fn install(repo: &Path, names: &[String], force: bool) -> Result<Vec<(Hook, HookStatus)>> {
+    for name in names {
+        if !HOOKS.iter().any(|h| h.name == name) {
+            let known: Vec<&str> = HOOKS.iter().map(|h| h.name).collect();
+            return Err(anyhow!(
+                "Unknown hook '{}' (expected one of: {})",
+                name,
+                known.join(", ")
+            ));
+        }
+    }
+    let selected = HOOKS.iter().filter(|h| {
+        if names.is_empty() {
+            h.default
+        } else {
+            names.iter().any(|n| n == h.name)
+        }
+    });
+
+    let dir = hooks_dir(repo)?;
+    std::fs::create_dir_all(&dir)?;
+
+    let mut results = Vec::new();
+    for hook in selected {
+        let path = dir.join(hook.name);
+
+        let status = if !path.exists() {
+            HookStatus::Installed
+        } else if is_ours(&path) || force {
+            HookStatus::Updated
+        } else {
+            results.push((*hook, HookStatus::Skipped));
+            continue;
+        };
+
+        std::fs::write(&path, script(hook))?;
+        make_executable(&path)?;
+        results.push((*hook, status));
+    }
+
+    Ok(results)
+}
+
+/// Remove the hooks arf installed, leaving any others in place.
+pub fn uninstall(repo: &Path) -> Result<Vec<(Hook, HookStatus)>> {
+    let dir = hooks_dir(repo)?;
+
+    let mut results = Vec::new();
+    for hook in HOOKS {
+        let path = dir.join(hook.name);
+
+        let status = if !path.exists() {
+            HookStatus::Missing
+        } else if is_ours(&path) {
+            std::fs::remove_file(&path)?;
+            HookStatus::Removed
+        } else {
+            HookStatus::Skipped
+        };
+        results.push((*hook, status));
+    }
+
+    Ok(results)
+}
+
+/// Parse `post-rewrite` input: one "<old-sha> <new-sha> [extra]" per line.
+pub fn parse_rewrite_pairs(input: &str) -> Result<Vec<(String, String)>> {
+    input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(old), Some(new)) => Ok((old.to_string(), new.to_string())),
+                _ => Err(anyhow!("Expected '<old-sha> <new-sha>', got: {}", line)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut perms = std::fs::metadata(path)?.permissions();
+    perms.set_mode(0o755);
+    std::fs::set_permissions(path, perms)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_pairs() {
+        let input = "aaa111 bbb222\n\n  ccc333\tddd444 extra\n";
+        let pairs = parse_rewrite_pairs(input).unwrap();
+        assert_eq!(
+            pairs,
+            [
+                ("aaa111".to_string(), "bbb222".to_string()),
+                ("ccc333".to_string(), "ddd444".to_string()),
+            ]
+        );
+        assert!(parse_rewrite_pairs("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rewrite_pairs_need_both_shas() {
+        let err = parse_rewrite_pairs("aaa111 bbb222\nccc333\n").unwrap_err();
+        assert!(err.to_string().contains("got: ccc333"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Record IDs in the style of ULIDs: a 48-bit millisecond timestamp followed
+//! by 80 random bits, written as 26 characters of Crockford base32. IDs sort
+//! by creation time and are safe to use as file names.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Length of a generated ID.
+pub const LEN: usize = 26;
+
+/// Generate a new ID.
+pub fn generate() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    encode(now.as_millis() as u64, random())
+}
+
+/// The ID for `millis` and the low 80 bits of `bits`. For IDs that must come
+/// out the same every time, e.g. of records derived from a commit.
+pub fn encode(millis: u64, bits: u128) -> String {
+    let millis = millis as u128 & ((1 << 48) - 1);
+    let value = (millis << 80) | (bits & ((1 << 80) - 1));
+
+    (0..LEN)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Whether `s` looks like a generated ID.
+pub fn is_valid(s: &str) -> bool {
+    s.len() == LEN && s.bytes().all(|b| ALPHABET.contains(&b))
+}
+
+/// 128 random bits without pulling in a dependency: std seeds every
+/// `RandomState` from the OS, and the counter keeps IDs from one process
+/// distinct.
+fn random() -> u128 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let word = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u32(std::process::id());
+        hasher.finish() as u128
+    };
+    (word() << 64) | word()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_ids_are_valid_and_distinct() {
+        let ids: Vec<String> = (0..1000).map(|_| generate()).collect();
+        assert!(ids.iter().all(|id| is_valid(id)));
+        let unique: std::collections::HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+    }
+
+    #[test]
+    fn ids_sort_by_time() {
+        let first = generate();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = generate();
+        assert!(first < second);
+
+        // The time decides, whatever the random bits
+        assert!(encode(1, u128::MAX) < encode(2, 0));
+        assert!(encode(1 << 47, 0) > encode((1 << 47) - 1, u128::MAX));
+    }
+
+    #[test]
+    fn encode_is_fixed_width() {
+        assert_eq!(encode(0, 0), "0".repeat(LEN));
+        assert_eq!(encode(0, 31), format!("{}Z", "0".repeat(LEN - 1)));
+        assert_eq!(
+            encode(u64::MAX, u128::MAX),
+            format!("7{}", "Z".repeat(LEN - 1))
+        );
+        assert_eq!(encode(5, 7), encode(5, 7));
+    }
+
+    #[test]
+    fn validity() {
+        assert!(is_valid("01M53SY77BE8MVQ5DPM0ZAECTW"));
+        assert!(!is_valid("01M53SY77BE8MVQ5DPM0ZAECT"));
+        assert!(!is_valid("01m53sy77be8mvq5dpm0zaectw"));
+        assert!(!is_valid("01M53SY77BE8MVQ5DPM0ZAECTU"));
+    }
+}
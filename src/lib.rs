@@ -0,0 +1,39 @@
+//! Agent Reasoning Format - track AI reasoning alongside git.
+//!
+//! Records live on an orphan `arf` branch, organized by the commit they
+//! explain. The branch can be checked out at `.arf/` or accessed purely
+//! through git objects (see [`backend`]). Settings are layered across the
+//! branch, git config, and the environment (see [`config`]). This crate
+//! exposes the record type and a store for reading and writing them, so
+//! other tools can work with ARF without shelling out to the `arf` binary.
+
+pub mod backend;
+pub mod check;
+pub mod config;
+pub mod coverage;
+pub mod doctor;
+pub mod embedded;
+pub mod git;
+pub mod hooks;
+pub mod id;
+pub mod record;
+pub mod schema;
+pub mod search;
+pub mod spec;
+pub mod store;
+pub mod sync;
+pub mod validate;
+
+pub use config::Config;
+pub use record::{
+    ArfRecord, InputFormat, Outcome, OutcomeStatus, Retraction, RECORD_SCHEMA_VERSION,
+};
+pub use schema::Format;
+pub use store::{Migration, Query, RecordStore, Storage, StoredRecord};
+
+/// Default name of the orphan branch that holds ARF records (see
+/// [`config::Config::branch`]).
+pub const ARF_BRANCH: &str = "arf";
+
+/// Notes ref used when records are stored as git notes.
+pub const NOTES_REF: &str = "refs/notes/arf";
@@ -1,21 +1,30 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
-use clap::{Parser, Subcommand};
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
+use arf::check::{self, Rules};
+use arf::config::{self, Scope};
+use arf::coverage::Coverage;
+use arf::doctor;
+use arf::embedded;
+use arf::hooks::{self, HookStatus};
+use arf::schema::{
+    self, ConfigItem, CoverageItem, DiffItem, GraphItem, LogItem, RecordEntry, SpecItem,
 };
-use ratatui::{
-    prelude::*,
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+use arf::search::{self, Index};
+use arf::spec::{self, Spec};
+use arf::store::parse_time;
+use arf::sync::{self, Conflict, Prefer, Pulled, Pushed};
+use arf::validate::{self, Lint, Report};
+use arf::{
+    git, id, ArfRecord, Config, Format, InputFormat, Outcome, OutcomeStatus, Query, RecordStore,
+    Retraction, Storage, NOTES_REF,
 };
-use serde::{Deserialize, Serialize};
-use std::io::stdout;
-use std::path::Path;
-use std::process::Command;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use crossterm::style::Stylize;
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
 
-const ARF_BRANCH: &str = "arf";
+mod tui;
 
 #[derive(Parser)]
 #[command(name = "arf")]
@@ -23,12 +32,28 @@ const ARF_BRANCH: &str = "arf";
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Output format for log, graph, diff, and spec: text, json, toml, or ndjson
+    #[arg(long, global = true, default_value = "text")]
+    format: Format,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize ARF tracking (creates orphan branch)
-    Init,
+    Init {
+        /// Where to keep records: "branch" (the arf branch) or "notes" (refs/notes/arf)
+        #[arg(long, default_value = "branch")]
+        storage: Storage,
+
+        /// Don't mount the branch at .arf/; read and write git objects directly
+        #[arg(long)]
+        no_checkout: bool,
+
+        /// Name the ARF branch something other than "arf" (saved as arf.branch)
+        #[arg(long, value_name = "NAME")]
+        branch: Option<String>,
+    },
 
     /// Manage specs (task definitions)
     Spec {
@@ -39,12 +64,24 @@ enum Commands {
     /// Record a reasoning entry
     Record {
         /// What action is being taken (required)
-        #[arg(long)]
-        what: String,
+        #[arg(long, required_unless_present_any = ["from_file", "stdin"])]
+        what: Option<String>,
 
         /// Why this approach (required)
-        #[arg(long)]
-        why: String,
+        #[arg(long, required_unless_present_any = ["from_file", "stdin"])]
+        why: Option<String>,
+
+        /// Read the record from a file (TOML, JSON, or ### What/### Why markdown)
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["what", "why", "stdin"])]
+        from_file: Option<PathBuf>,
+
+        /// Read the record from stdin (same formats as --from-file)
+        #[arg(long, conflicts_with_all = ["what", "why"])]
+        stdin: bool,
+
+        /// Format of --from-file/--stdin input: toml, json, or markdown (default: detect)
+        #[arg(long, value_name = "FORMAT")]
+        input_format: Option<InputFormat>,
 
         /// How it will be implemented (optional)
         #[arg(long)]
@@ -57,19 +94,113 @@ enum Commands {
         /// Link to specific commit (defaults to HEAD)
         #[arg(short, long)]
         commit: Option<String>,
+
+        /// Stage for the next commit instead of attaching to HEAD
+        #[arg(long, conflicts_with = "commit")]
+        pending: bool,
+
+        /// Extra metadata for the [context] table, e.g. issue=42 (repeatable)
+        #[arg(long = "context", value_name = "KEY=VALUE", value_parser = ArfRecord::parse_context)]
+        context: Vec<(String, toml::Value)>,
+    },
+
+    /// Attach pending records to a commit (run by the post-commit hook)
+    Bind {
+        /// Commit to attach them to (defaults to HEAD)
+        #[arg(short, long)]
+        commit: Option<String>,
+    },
+
+    /// Show a single record
+    Show {
+        /// Record ID or unique prefix (as shown by 'arf log')
+        id: String,
+    },
+
+    /// Report the outcome of a recorded action
+    Outcome {
+        /// Record ID or unique prefix (as shown by 'arf log')
+        record: String,
+
+        /// How it went: success, failure, or partial
+        #[arg(long)]
+        status: OutcomeStatus,
+
+        /// Details, e.g. what failed
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Edit a record in $EDITOR and commit the change
+    Edit {
+        /// Record ID or unique prefix
+        id: String,
+    },
+
+    /// Mark a record as withdrawn, keeping it in history
+    Retract {
+        /// Record ID or unique prefix
+        id: String,
+
+        /// Why the reasoning no longer stands
+        #[arg(long)]
+        reason: String,
     },
 
-    /// Show reasoning records
+    /// Show reasoning records (filters combine: every one given must match)
     Log {
         /// Show records for specific commit
         #[arg(short, long)]
         commit: Option<String>,
 
+        /// Only records written by this agent
+        #[arg(long)]
+        agent: Option<String>,
+
+        /// Only records from this time on (2026-01-31, RFC 3339, today, 7d, 12h, ...)
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only records up to this time (same forms as --since)
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only records with this outcome: success, failure, or partial
+        #[arg(long)]
+        outcome: Option<OutcomeStatus>,
+
+        /// Only records whose what/why/how/backup contains this text (case-insensitive)
+        #[arg(long)]
+        grep: Option<String>,
+
+        /// Only records whose commit touched this path (repeatable)
+        #[arg(long = "path")]
+        paths: Vec<String>,
+
+        /// Only records for commits on a branch or in a range (main..feature)
+        #[arg(long, alias = "range")]
+        branch: Option<String>,
+
         /// Limit number of records
         #[arg(short, long, default_value = "10")]
         limit: usize,
     },
 
+    /// Full-text search over what, why, how, backup, and context
+    Search {
+        /// Words to look for; records matching more of them rank higher
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// Maximum number of results
+        #[arg(short, long, default_value = "10")]
+        limit: usize,
+
+        /// Rebuild the index from scratch instead of updating it
+        #[arg(long)]
+        rebuild: bool,
+    },
+
     /// Sync ARF branch with remote
     Sync {
         /// Push local records to remote
@@ -79,6 +210,18 @@ enum Commands {
         /// Pull remote records
         #[arg(long)]
         pull: bool,
+
+        /// Settle records changed on both sides by keeping this side's version (local or remote)
+        #[arg(long, value_name = "SIDE")]
+        prefer: Option<Prefer>,
+
+        /// Sync with this remote only (default: arf.remote, then mirrors from arf.mirror)
+        #[arg(long, value_name = "NAME")]
+        remote: Option<String>,
+
+        /// Also pull and merge the ARF branch of every other remote (refs/remotes/*/arf)
+        #[arg(long)]
+        all_remotes: bool,
     },
 
     /// Show git commits with ARF reasoning
@@ -88,6 +231,44 @@ enum Commands {
         limit: usize,
     },
 
+    /// Fail if commits matching the check rules lack valid reasoning
+    Check {
+        /// Commits to check: a range (origin/main..HEAD), a branch, or revisions separated by spaces
+        #[arg(value_name = "RANGE")]
+        range: String,
+
+        /// Require reasoning for commits with a matching Co-authored-by trailer
+        #[arg(long, value_name = "PATTERN")]
+        co_author: Vec<String>,
+
+        /// Require reasoning for commits whose committer email matches
+        #[arg(long, value_name = "PATTERN")]
+        committer: Vec<String>,
+
+        /// Require reasoning for commits touching these paths
+        #[arg(long, value_name = "PATHSPEC")]
+        path: Vec<String>,
+    },
+
+    /// Report which commits in a range have reasoning
+    Coverage {
+        /// Commits to check: a range (main..HEAD), a branch, or revisions separated by spaces
+        #[arg(value_name = "RANGE")]
+        range: String,
+
+        /// Exit with an error if less than this percentage of commits is covered
+        #[arg(long, value_name = "PERCENT")]
+        min_coverage: Option<f64>,
+
+        /// Only count commits whose author matches (as with git log --author)
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Leave out merge commits
+        #[arg(long)]
+        no_merges: bool,
+    },
+
     /// Show diff with ARF reasoning context
     Diff {
         /// Commit to diff (defaults to HEAD)
@@ -101,6 +282,155 @@ enum Commands {
 
     /// Interactive TUI browser
     Browse,
+
+    /// Re-attach records to rewritten commits (reads post-rewrite hook input)
+    Rewrite {
+        /// What rewrote the commits, as git passes it to the hook (amend or rebase)
+        kind: Option<String>,
+
+        /// Copy records to the new commits instead of moving them
+        #[arg(long)]
+        copy: bool,
+    },
+
+    /// Write records to files: one TOML stream, or one file per record
+    Export {
+        /// Only records for commits in this range (main..feature, a branch, ...)
+        #[arg(value_name = "RANGE")]
+        range: Option<String>,
+
+        /// Bundle the records into one TOML stream of [[record]] entries
+        #[arg(long)]
+        stream: bool,
+
+        /// File to write the stream to (default: stdout), or without --stream the directory
+        #[arg(short, long, required_unless_present = "stream")]
+        output: Option<PathBuf>,
+    },
+
+    /// Import records from files (TOML streams or single records) or commit messages
+    Import {
+        /// Record files to import; "-" reads stdin. Each record goes to the commit it names.
+        #[arg(
+            value_name = "FILE",
+            required_unless_present = "from_commits",
+            conflicts_with = "from_commits"
+        )]
+        files: Vec<PathBuf>,
+
+        /// Instead, import records embedded in the messages of these commits
+        /// (```arf blocks, What:/Why: trailers)
+        #[arg(long, value_name = "RANGE")]
+        from_commits: Option<String>,
+
+        /// Show what would be imported without writing
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check records against the ARF schema and lint rules
+    Validate {
+        /// Record files to check (default: every stored record); "-" reads stdin
+        paths: Vec<PathBuf>,
+
+        /// Fail on lint warnings too
+        #[arg(long)]
+        strict: bool,
+
+        /// Only check the schema
+        #[arg(long, conflicts_with = "strict")]
+        no_lint: bool,
+    },
+
+    /// Read and change settings (.arfconfig, git config, ARF_* variables)
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Manage git hooks installed by arf
+    Hooks {
+        #[command(subcommand)]
+        command: HookCommands,
+    },
+
+    /// Check records for parse errors, mismatched or dangling commits, and uncommitted changes
+    #[command(alias = "fsck")]
+    Doctor {
+        /// Repair issues that can be fixed mechanically
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Move records from short-SHA directories to full commit SHAs
+    Migrate {
+        /// Show what would move without committing
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print a setting's value (one line per value for lists)
+    Get { key: String },
+
+    /// Change a setting in this repository's git config
+    Set {
+        key: String,
+
+        /// One or more values (lists only take several)
+        #[arg(required = true)]
+        values: Vec<String>,
+
+        /// Add to a list instead of replacing it
+        #[arg(long)]
+        add: bool,
+
+        /// Write to your global git config instead
+        #[arg(long, conflicts_with = "shared")]
+        global: bool,
+
+        /// Write to .arfconfig on the ARF branch, shared with everyone who syncs
+        #[arg(long)]
+        shared: bool,
+    },
+
+    /// Remove a setting
+    Unset {
+        key: String,
+
+        /// Remove it from your global git config instead
+        #[arg(long, conflicts_with = "shared")]
+        global: bool,
+
+        /// Remove it from .arfconfig on the ARF branch instead
+        #[arg(long)]
+        shared: bool,
+    },
+
+    /// Show every setting in effect and where it comes from
+    List {
+        /// Include unset settings, with what each does
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum HookCommands {
+    /// Install arf's git hooks
+    Install {
+        /// Hooks to install (default: post-rewrite and post-commit; pre-push runs arf check)
+        hooks: Vec<String>,
+
+        /// Overwrite existing hooks that weren't installed by arf
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Remove arf's git hooks
+    Uninstall,
 }
 
 #[derive(Subcommand)]
@@ -113,170 +443,333 @@ enum SpecCommands {
         /// Spec name (without .arf extension)
         name: String,
     },
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ArfRecord {
-    what: String,
-    why: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    how: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    backup: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    outcome: Option<String>,
-    timestamp: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    commit: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    agent: Option<String>,
+    /// Create a spec from a template and commit it to the ARF branch
+    New {
+        /// Spec name (letters, digits, '-', '_', '.')
+        name: String,
+
+        /// The task, as a concrete change
+        #[arg(long)]
+        what: Option<String>,
+
+        /// The problem it solves
+        #[arg(long)]
+        why: Option<String>,
+
+        /// Open the template in $EDITOR even when --what and --why are given
+        #[arg(long)]
+        edit: bool,
+    },
+
+    /// Edit a spec in $EDITOR and commit the change to the ARF branch
+    Edit {
+        /// Spec name (without .arf extension)
+        name: String,
+    },
+
+    /// Check specs against the spec schema (all of them by default)
+    Validate {
+        /// Spec names (without .arf extension)
+        names: Vec<String>,
+
+        /// Exit non-zero on warnings too
+        #[arg(long)]
+        strict: bool,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    // Work from anywhere in the checkout; bare repositories have no top
+    let toplevel = git::toplevel(Path::new("."));
+    let repo = toplevel.as_deref().unwrap_or(Path::new("."));
+    // Settings that can't be used are skipped; say so once per command
+    if let Ok(config) = Config::load(repo) {
+        for warning in &config.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
 
     match cli.command {
-        Commands::Init => cmd_init()?,
+        Commands::Init {
+            storage,
+            no_checkout,
+            branch,
+        } => cmd_init(repo, storage, !no_checkout, branch)?,
         Commands::Spec { command } => match command {
-            SpecCommands::List => cmd_spec_list()?,
-            SpecCommands::Show { name } => cmd_spec_show(&name)?,
+            SpecCommands::List => cmd_spec_list(repo, cli.format)?,
+            SpecCommands::Show { name } => cmd_spec_show(repo, &name, cli.format)?,
+            SpecCommands::New {
+                name,
+                what,
+                why,
+                edit,
+            } => cmd_spec_new(repo, &name, what.as_deref(), why.as_deref(), edit)?,
+            SpecCommands::Edit { name } => cmd_spec_edit(repo, &name)?,
+            SpecCommands::Validate { names, strict } => cmd_spec_validate(repo, &names, strict)?,
         },
         Commands::Record {
             what,
             why,
+            from_file,
+            stdin,
+            input_format,
             how,
             backup,
             commit,
-        } => cmd_record(what, why, how, backup, commit)?,
-        Commands::Log { commit, limit } => cmd_log(commit, limit)?,
-        Commands::Sync { push, pull } => cmd_sync(push, pull)?,
-        Commands::Graph { limit } => cmd_graph(limit)?,
-        Commands::Diff { commit, full } => cmd_diff(commit, full)?,
-        Commands::Browse => cmd_browse()?,
+            pending,
+            context,
+        } => {
+            let mut record = match (what, why) {
+                (Some(what), Some(why)) => ArfRecord::new(what, why),
+                _ if stdin => read_record(None, input_format)?,
+                _ => read_record(from_file.as_deref(), input_format)?,
+            };
+            // Flags add to (or override) what the input provided
+            record.how = how.or(record.how);
+            record.backup = backup.or(record.backup);
+            record.commit = commit.or(record.commit);
+            record.context.extend(context);
+            cmd_record(repo, record, pending)?
+        }
+        Commands::Bind { commit } => cmd_bind(repo, commit)?,
+        Commands::Show { id } => cmd_show(repo, &id)?,
+        Commands::Outcome {
+            record,
+            status,
+            reason,
+        } => cmd_outcome(repo, &record, status, reason)?,
+        Commands::Edit { id } => cmd_edit(repo, &id)?,
+        Commands::Retract { id, reason } => cmd_retract(repo, &id, reason)?,
+        Commands::Log {
+            commit,
+            agent,
+            since,
+            until,
+            outcome,
+            grep,
+            paths,
+            branch,
+            limit,
+        } => {
+            let query = Query {
+                commit,
+                agent,
+                since,
+                until,
+                outcome,
+                grep,
+                paths,
+                range: branch,
+                limit: Some(limit),
+            };
+            cmd_log(repo, &query, cli.format)?
+        }
+        Commands::Search {
+            query,
+            limit,
+            rebuild,
+        } => cmd_search(repo, &query.join(" "), limit, rebuild)?,
+        Commands::Sync {
+            push,
+            pull,
+            prefer,
+            remote,
+            all_remotes,
+        } => cmd_sync(repo, push, pull, prefer, remote, all_remotes)?,
+        Commands::Graph { limit } => cmd_graph(repo, limit, cli.format)?,
+        Commands::Check {
+            range,
+            co_author,
+            committer,
+            path,
+        } => {
+            let rules = Rules {
+                co_authors: co_author,
+                committers: committer,
+                paths: path,
+            };
+            cmd_check(repo, &range, rules)?
+        }
+        Commands::Coverage {
+            range,
+            min_coverage,
+            author,
+            no_merges,
+        } => cmd_coverage(
+            repo,
+            &range,
+            min_coverage,
+            author.as_deref(),
+            no_merges,
+            cli.format,
+        )?,
+        Commands::Diff { commit, full } => cmd_diff(repo, commit, full, cli.format)?,
+        Commands::Browse => tui::run(repo)?,
+        Commands::Rewrite { kind, copy } => cmd_rewrite(repo, kind, copy)?,
+        Commands::Export {
+            range,
+            stream,
+            output,
+        } => cmd_export(repo, range, stream, output)?,
+        Commands::Import {
+            files,
+            from_commits,
+            dry_run,
+        } => cmd_import(repo, &files, from_commits.as_deref(), dry_run)?,
+        Commands::Validate {
+            paths,
+            strict,
+            no_lint,
+        } => cmd_validate(repo, &paths, strict, no_lint)?,
+        Commands::Config { command } => match command {
+            ConfigCommands::Get { key } => cmd_config_get(repo, &key)?,
+            ConfigCommands::Set {
+                key,
+                values,
+                add,
+                global,
+                shared,
+            } => cmd_config_set(repo, &key, &values, add, scope(global, shared))?,
+            ConfigCommands::Unset {
+                key,
+                global,
+                shared,
+            } => cmd_config_set(repo, &key, &[], false, scope(global, shared))?,
+            ConfigCommands::List { all } => cmd_config_list(repo, all, cli.format)?,
+        },
+        Commands::Hooks { command } => match command {
+            HookCommands::Install { hooks, force } => cmd_hooks_install(repo, &hooks, force)?,
+            HookCommands::Uninstall => cmd_hooks_uninstall(repo)?,
+        },
+        Commands::Doctor { fix } => cmd_doctor(repo, fix)?,
+        Commands::Migrate { dry_run } => cmd_migrate(repo, dry_run)?,
     }
 
     Ok(())
 }
 
-fn cmd_init() -> Result<()> {
-    // Check if we're in a git repo
-    let status = Command::new("git")
-        .args(["rev-parse", "--git-dir"])
-        .output()?;
-
-    if !status.status.success() {
-        return Err(anyhow!("Not a git repository. Run 'git init' first."));
-    }
-
-    // Check if arf branch already exists
-    let branch_check = Command::new("git")
-        .args(["rev-parse", "--verify", ARF_BRANCH])
-        .output()?;
-
-    if branch_check.status.success() {
-        println!("✓ ARF branch '{}' already exists", ARF_BRANCH);
-        return Ok(());
-    }
-
+fn cmd_init(repo: &Path, storage: Storage, checkout: bool, branch: Option<String>) -> Result<()> {
     println!("Initializing ARF...");
-
-    // Create orphan branch using worktree
-    let output = Command::new("git")
-        .args(["worktree", "add", "--orphan", "-b", ARF_BRANCH, ".arf"])
-        .output()?;
-
-    if !output.status.success() {
-        return Err(anyhow!(
-            "Failed to create ARF branch: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+    if let Some(ref branch) = branch {
+        git::run(repo, &["check-ref-format", "--branch", branch])
+            .map_err(|_| anyhow!("'{}' is not a valid branch name", branch))?;
+        git::run(repo, &["config", "arf.branch", branch])?;
     }
 
-    // Create initial structure
-    std::fs::create_dir_all(".arf/records")?;
-    std::fs::create_dir_all(".arf/specs")?;
-
-    // Create README in arf branch
-    let readme = r#"# ARF Records
-
-This branch contains Agent Reasoning Format records.
-
-Records are organized by commit SHA:
-```
-records/
-  <commit-sha>/
-    <agent>-<timestamp>.toml
-```
-
-See https://github.com/ducks/arf for the ARF specification.
-"#;
-    std::fs::write(".arf/README.md", readme)?;
-
-    // Commit initial structure
-    let add = Command::new("git")
-        .args(["add", "."])
-        .current_dir(".arf")
-        .output()?;
-
-    if !add.status.success() {
-        return Err(anyhow!("Failed to stage files"));
-    }
-
-    let commit = Command::new("git")
-        .args(["commit", "-m", "Initialize ARF"])
-        .current_dir(".arf")
-        .output()?;
+    let Some(store) = RecordStore::init(repo, storage, checkout)? else {
+        match storage {
+            Storage::Branch => println!(
+                "✓ ARF branch '{}' already exists",
+                Config::load(repo)?.branch
+            ),
+            Storage::Notes => {
+                println!("✓ ARF notes already configured ({})", NOTES_REF);
+                install_rewrite_hook(repo)?;
+            }
+        }
+        return Ok(());
+    };
 
-    if !commit.status.success() {
-        // Might be empty, that's ok
-        let stderr = String::from_utf8_lossy(&commit.stderr);
-        if !stderr.contains("nothing to commit") {
-            return Err(anyhow!("Failed to commit: {}", stderr));
+    match storage {
+        Storage::Branch => {
+            println!("✓ Created ARF branch '{}'", store.config().branch);
+            if let Some(dir) = store.worktree() {
+                let shown = dir.strip_prefix(repo).unwrap_or(&dir);
+                println!("✓ Mounted at {}/", shown.display());
+            }
+        }
+        Storage::Notes => {
+            println!("✓ Storing records as notes in {}", NOTES_REF);
+            println!("✓ Notes follow rebases and amends (notes.rewriteRef)");
+            install_rewrite_hook(repo)?;
+            println!("  View with: git log --notes=arf");
         }
     }
-
-    println!("✓ Created ARF branch '{}'", ARF_BRANCH);
-    println!("✓ Mounted at .arf/");
     println!();
     println!("Next: arf record --what 'action' --why 'reason'");
 
     Ok(())
 }
 
-fn cmd_spec_list() -> Result<()> {
-    let specs_dir = Path::new(".arf/specs");
+/// git copies notes to rewritten commits but leaves the originals behind;
+/// the post-rewrite hook removes them so records don't show up twice.
+fn install_rewrite_hook(repo: &Path) -> Result<()> {
+    if hooks::is_installed(repo, "post-rewrite") {
+        return Ok(());
+    }
+    for (hook, status) in hooks::install(repo, &["post-rewrite".to_string()], false)? {
+        match status {
+            HookStatus::Installed | HookStatus::Updated => {
+                println!("✓ Installed {} hook ({})", hook.name, hook.description)
+            }
+            _ => eprintln!(
+                "Warning: an existing {} hook was not installed by arf, so notes left on \
+                 amended and rebased commits will duplicate records. Run \
+                 'arf hooks install --force' to replace it, or call 'arf rewrite' from it.",
+                hook.name
+            ),
+        }
+    }
+    Ok(())
+}
 
-    if !specs_dir.exists() {
+fn cmd_spec_list(repo: &Path, format: Format) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    if !store.is_initialized() {
         return Err(anyhow!(
             "ARF not initialized or no specs directory. Run 'arf init' first."
         ));
     }
 
-    let mut specs: Vec<String> = Vec::new();
+    let specs = store.specs()?;
 
-    if let Ok(entries) = std::fs::read_dir(specs_dir) {
-        for entry in entries.filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.extension().is_some_and(|e| e == "arf") {
-                if let Some(name) = path.file_stem() {
-                    specs.push(name.to_string_lossy().to_string());
-                }
-            }
-        }
+    if format != Format::Text {
+        let items: Vec<SpecItem> = specs
+            .into_iter()
+            .map(|name| SpecItem {
+                name,
+                content: None,
+            })
+            .collect();
+        print!("{}", schema::render(format, "spec", &items)?);
+        return Ok(());
     }
 
     if specs.is_empty() {
-        println!("No specs found in .arf/specs/");
+        println!(
+            "No specs found in specs/ on the '{}' branch",
+            store.config().branch
+        );
         println!();
-        println!("Generate specs with: lok spec \"your task description\"");
+        println!("Create one with: arf spec new <name> --what 'task' --why 'reason'");
         return Ok(());
     }
 
-    specs.sort();
-
     println!("Specs ({}):\n", specs.len());
+    let width = specs.iter().map(|n| n.len()).max().unwrap_or(0);
     for name in &specs {
-        println!("  {}", name);
+        // Specs that don't follow the schema (e.g. from other tools) just get a name
+        match store.spec(name).ok().and_then(|c| Spec::from_toml(&c).ok()) {
+            Some(spec) => {
+                let (done, total) = spec.progress();
+                let progress = if total > 0 {
+                    format!(" {}/{}", done, total)
+                } else {
+                    String::new()
+                };
+                println!(
+                    "  {:width$}  [{}{}] {}",
+                    name,
+                    spec.status,
+                    progress,
+                    spec.what,
+                    width = width
+                );
+            }
+            None => println!("  {}", name),
+        }
     }
     println!();
     println!("Show details: arf spec show <name>");
@@ -284,169 +777,483 @@ fn cmd_spec_list() -> Result<()> {
     Ok(())
 }
 
-fn cmd_spec_show(name: &str) -> Result<()> {
-    let specs_dir = Path::new(".arf/specs");
-    let spec_path = specs_dir.join(format!("{}.arf", name));
+fn cmd_spec_show(repo: &Path, name: &str, format: Format) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let content = store.spec(name)?;
 
-    if !spec_path.exists() {
-        return Err(anyhow!("Spec not found: {}", name));
+    if format != Format::Text {
+        let item = SpecItem {
+            name: name.to_string(),
+            content: Some(content),
+        };
+        print!("{}", schema::render(format, "spec", &[item])?);
+        return Ok(());
     }
 
-    let content = std::fs::read_to_string(&spec_path)?;
-
     println!("═══════════════════════════════════════════════════════════════");
     println!("Spec: {}", name);
     println!("═══════════════════════════════════════════════════════════════");
     println!();
-    print!("{}", content);
+
+    let Ok(spec) = Spec::from_toml(&content) else {
+        print!("{}", content);
+        return Ok(());
+    };
+    println!("what:   {}", spec.what);
+    println!("why:    {}", spec.why);
+    if let Some(ref how) = spec.how {
+        println!("how:    {}", how);
+    }
+    if let Some(ref backup) = spec.backup {
+        println!("backup: {}", backup);
+    }
+    println!("status: {}", spec.status);
+    if let Some(ref created) = spec.created {
+        println!("created: {}", created);
+    }
+    if !spec.acceptance.is_empty() {
+        println!();
+        println!("Acceptance:");
+        for item in &spec.acceptance {
+            println!("  - {}", item);
+        }
+    }
+    if !spec.tasks.is_empty() {
+        let (done, total) = spec.progress();
+        println!();
+        println!("Tasks ({}/{} done):", done, total);
+        for task in &spec.tasks {
+            let mark = if task.done { "x" } else { " " };
+            println!("  [{}] {}", mark, task.what);
+            if let Some(ref how) = task.how {
+                println!("      {}", how);
+            }
+        }
+    }
+    if !spec.context.is_empty() {
+        println!();
+        println!("Context:");
+        for (key, value) in &spec.context {
+            println!("  {} = {}", key, value);
+        }
+    }
 
     Ok(())
 }
 
-fn cmd_record(
-    what: String,
-    why: String,
-    how: Option<String>,
-    backup: Option<String>,
-    commit: Option<String>,
+fn cmd_spec_new(
+    repo: &Path,
+    name: &str,
+    what: Option<&str>,
+    why: Option<&str>,
+    edit: bool,
 ) -> Result<()> {
-    // Check if arf is initialized
-    if !Path::new(".arf").exists() {
+    spec::check_name(name)?;
+    let store = RecordStore::open(repo)?;
+    if store.storage() == Storage::Branch && !store.is_initialized() {
         return Err(anyhow!("ARF not initialized. Run 'arf init' first."));
     }
+    if store.spec(name).is_ok() {
+        return Err(anyhow!(
+            "Spec '{}' already exists; change it with 'arf spec edit {}'",
+            name,
+            name
+        ));
+    }
 
-    // Get commit SHA (default to HEAD)
-    let commit_sha = match commit {
-        Some(c) => c,
-        None => {
-            let output = Command::new("git").args(["rev-parse", "HEAD"]).output()?;
-            if !output.status.success() {
-                return Err(anyhow!("Failed to get HEAD commit"));
-            }
-            String::from_utf8_lossy(&output.stdout).trim().to_string()
+    let mut content = spec::template(what, why);
+    if edit || what.is_none() || why.is_none() {
+        if !std::io::stdin().is_terminal() {
+            return Err(anyhow!(
+                "Give --what and --why, or run in a terminal to fill in the template"
+            ));
         }
-    };
-
-    let short_sha = &commit_sha[..8.min(commit_sha.len())];
-
-    // Create record
-    let record = ArfRecord {
-        what,
-        why,
-        how,
-        backup,
-        outcome: None,
-        timestamp: Utc::now().to_rfc3339(),
-        commit: Some(commit_sha.clone()),
-        agent: std::env::var("ARF_AGENT").ok(),
-    };
-
-    // Create directory for this commit
-    let record_dir = format!(".arf/records/{}", short_sha);
-    std::fs::create_dir_all(&record_dir)?;
-
-    // Generate filename
-    let timestamp = Utc::now().format("%Y%m%d-%H%M%S");
-    let agent = record.agent.as_deref().unwrap_or("unknown");
-    let filename = format!("{}/{}-{}.toml", record_dir, agent, timestamp);
-
-    // Write record
-    let content = toml::to_string_pretty(&record)?;
-    std::fs::write(&filename, &content)?;
+        content = edit_spec(name, &content)?;
+    }
+    let spec = Spec::from_toml(&content)?;
 
-    // Commit to arf branch
-    let add = Command::new("git")
-        .args(["add", "."])
-        .current_dir(".arf")
-        .output()?;
+    store.save_spec(name, &content, &format!("Spec: add {}", name))?;
+    println!("✓ Created spec {}: {}", name, spec.what);
+    println!("  Edit with: arf spec edit {}", name);
 
-    if !add.status.success() {
-        return Err(anyhow!("Failed to stage record"));
-    }
+    Ok(())
+}
 
-    let commit_msg = format!("Record: {}", record.what);
-    let commit_result = Command::new("git")
-        .args(["commit", "-m", &commit_msg])
-        .current_dir(".arf")
-        .output()?;
+fn cmd_spec_edit(repo: &Path, name: &str) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let original = store.spec(name)?;
+    let content = edit_spec(name, &original)?;
 
-    if !commit_result.status.success() {
-        let stderr = String::from_utf8_lossy(&commit_result.stderr);
-        if !stderr.contains("nothing to commit") {
-            return Err(anyhow!("Failed to commit record: {}", stderr));
-        }
+    if content == original {
+        println!("No changes to {}", name);
+        return Ok(());
     }
 
-    println!("✓ Recorded: {}", record.what);
-    println!("  Commit: {}", short_sha);
+    store.save_spec(name, &content, &format!("Spec: edit {}", name))?;
+    println!("✓ Edited spec {}", name);
 
     Ok(())
 }
 
-fn cmd_log(commit: Option<String>, limit: usize) -> Result<()> {
-    if !Path::new(".arf/records").exists() {
-        return Err(anyhow!("ARF not initialized. Run 'arf init' first."));
-    }
+/// Let the user edit a spec in their editor, and check the result. On an
+/// invalid spec the edited file is kept, and the error says where.
+fn edit_spec(name: &str, content: &str) -> Result<String> {
+    let path = temp_file(&format!("arf-spec-{}", name), content)?;
+    run_editor(&path)?;
+    let edited = std::fs::read_to_string(&path)?;
 
-    let records_dir = Path::new(".arf/records");
-    let mut all_records: Vec<(String, ArfRecord)> = Vec::new();
+    if let Err(e) = Spec::from_toml(&edited) {
+        return Err(anyhow!(
+            "{}\nYour changes are saved in {}",
+            e,
+            path.display()
+        ));
+    }
+    std::fs::remove_file(&path)?;
+    Ok(edited)
+}
 
-    // If specific commit requested, only look there
-    let dirs_to_check: Vec<_> = if let Some(ref sha) = commit {
-        let short = &sha[..8.min(sha.len())];
-        let path = records_dir.join(short);
-        if path.exists() {
-            vec![path]
-        } else {
-            println!("No records for commit {}", short);
-            return Ok(());
-        }
+fn cmd_spec_validate(repo: &Path, names: &[String], strict: bool) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let names = if names.is_empty() {
+        store.specs()?
     } else {
-        std::fs::read_dir(records_dir)?
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .filter(|p| p.is_dir())
-            .collect()
+        names.to_vec()
     };
 
-    // Read all records
-    for dir in dirs_to_check {
-        if let Ok(entries) = std::fs::read_dir(&dir) {
-            for entry in entries.filter_map(|e| e.ok()) {
-                let path = entry.path();
-                if path.extension().is_some_and(|e| e == "toml") {
-                    if let Ok(content) = std::fs::read_to_string(&path) {
-                        if let Ok(record) = toml::from_str::<ArfRecord>(&content) {
-                            let filename = path.file_name().unwrap().to_string_lossy().to_string();
-                            all_records.push((filename, record));
-                        }
-                    }
-                }
-            }
-        }
+    let mut reports = Vec::new();
+    for name in &names {
+        reports.push(spec::report(name, &store.spec(name)?));
+    }
+
+    let failing: Vec<&Report> = reports.iter().filter(|r| !r.problems.is_empty()).collect();
+    for report in &failing {
+        print_report(report);
     }
 
-    // Sort by timestamp (newest first)
-    all_records.sort_by(|a, b| b.1.timestamp.cmp(&a.1.timestamp));
+    let errors = reports.iter().filter(|r| r.has_errors()).count();
+    if failing.is_empty() {
+        println!("✓ {} spec(s) valid", reports.len());
+        return Ok(());
+    }
+    println!(
+        "{} of {} spec(s) invalid, {} with warnings",
+        errors,
+        reports.len(),
+        failing.len() - errors
+    );
+    if errors > 0 || strict {
+        std::process::exit(1);
+    }
 
-    // Limit
-    let records: Vec<_> = all_records.into_iter().take(limit).collect();
+    Ok(())
+}
 
-    if records.is_empty() {
-        println!("No ARF records found.");
+fn cmd_record(repo: &Path, mut record: ArfRecord, pending: bool) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    if record.agent.is_none() {
+        record.agent = store.config().agent.clone();
+    }
+    record.validate()?;
+    let lint = Lint::from_config(store.config());
+    let missing = lint.missing(&record);
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "This repository requires {} on every record (record.require)",
+            missing.join(", ")
+        ));
+    }
+    let warnings = lint.check(&record);
+
+    if pending {
+        store.stage(record.clone())?;
+        println!("✓ Staged: {}", record.what);
+        println!("  ID: {}", record.name());
+        if hooks::is_installed(repo, "post-commit") {
+            println!("  Attaches to your next commit");
+        } else {
+            println!("  Attach after committing with: arf bind (or run 'arf hooks install')");
+        }
+        print_lint(&warnings);
         return Ok(());
     }
 
-    println!("ARF Records ({}):\n", records.len());
+    record.commit = match record.commit {
+        Some(c) => Some(git::resolve_commit(repo, &c)?),
+        None => None,
+    };
 
-    for (_filename, record) in records {
-        let commit_str = record
-            .commit
-            .as_ref()
-            .map(|c| &c[..8.min(c.len())])
-            .unwrap_or("none");
+    let stored = store.write(record)?;
 
-        println!("commit {}", commit_str);
+    println!("✓ Recorded: {}", stored.record.what);
+    println!("  Commit: {}", stored.record.short_commit());
+    print_lint(&warnings);
+
+    Ok(())
+}
+
+fn print_lint(warnings: &[validate::Problem]) {
+    for warning in warnings {
+        println!("  warning: {}", warning);
+    }
+}
+
+/// Read a record for `arf record --from-file` (or stdin when `path` is
+/// `None` or `-`).
+fn read_record(path: Option<&Path>, format: Option<InputFormat>) -> Result<ArfRecord> {
+    let (content, extension) = match path {
+        Some(path) if path != Path::new("-") => (
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("Can't read {}: {}", path.display(), e))?,
+            path.extension().and_then(|e| e.to_str()),
+        ),
+        _ => {
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            (content, None)
+        }
+    };
+
+    let format = format.unwrap_or_else(|| InputFormat::detect(extension, &content));
+    ArfRecord::from_input(&content, format)
+}
+
+fn cmd_bind(repo: &Path, commit: Option<String>) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let bound = store.bind_pending(commit.as_deref().unwrap_or("HEAD"))?;
+
+    if let Some(first) = bound.first() {
+        println!(
+            "arf: Attached {} pending record(s) to {}",
+            bound.len(),
+            first.record.short_commit()
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_show(repo: &Path, reference: &str) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let stored = store.find(reference)?;
+    let record = &stored.record;
+
+    let commit = match record.commit {
+        Some(ref sha) => git::run(repo, &["log", "-1", "--oneline", sha])
+            .unwrap_or_else(|_| format!("{} (missing)", git::short_sha(sha))),
+        None => "none".to_string(),
+    };
+
+    println!("═══════════════════════════════════════════════════════════════");
+    println!("Record: {}", record.name());
+    println!("Commit: {}", commit);
+    println!("═══════════════════════════════════════════════════════════════");
+    println!();
+    print!("{}", record.to_toml()?);
+
+    Ok(())
+}
+
+fn cmd_outcome(
+    repo: &Path,
+    reference: &str,
+    status: OutcomeStatus,
+    reason: Option<String>,
+) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let stored = store.find(reference)?;
+
+    let mut record = stored.record.clone();
+    let previous = record.outcome.replace(Outcome::new(status, reason));
+    let message = format!("Outcome: {} for {}", status, record.what);
+    let updated = store.update(&stored, record, &message)?;
+
+    println!("✓ Outcome: {}", updated.record.what);
+    if let Some(previous) = previous {
+        println!("  Was: {}", previous);
+    }
+    if let Some(ref outcome) = updated.record.outcome {
+        println!("  Now: {}", outcome);
+    }
+
+    Ok(())
+}
+
+fn cmd_edit(repo: &Path, reference: &str) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let stored = store.find(reference)?;
+
+    let prefix = format!("arf-edit-{}", stored.record.name());
+    let path = temp_file(&prefix, &stored.record.to_toml()?)?;
+    run_editor(&path)?;
+    let content = std::fs::read_to_string(&path)?;
+
+    let record = match parse_edit(&stored.record, &content) {
+        Ok(record) => record,
+        Err(e) => {
+            return Err(anyhow!(
+                "{}\nYour changes are saved in {}",
+                e,
+                path.display()
+            ))
+        }
+    };
+    std::fs::remove_file(&path)?;
+
+    if record == stored.record {
+        println!("No changes to {}", record.name());
+        return Ok(());
+    }
+
+    let message = format!("Edit: {}", record.what);
+    let updated = store.update(&stored, record, &message)?;
+    println!("✓ Edited: {}", updated.record.what);
+    println!("  ID: {}", updated.record.name());
+
+    Ok(())
+}
+
+/// Write `content` to a new `<prefix>-<random>.toml` in the temp directory,
+/// readable only by the user. The file is always freshly created, so nobody
+/// else can plant or watch it.
+fn temp_file(prefix: &str, content: &str) -> Result<PathBuf> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    loop {
+        let name = format!("{}-{}.toml", prefix, id::generate().to_lowercase());
+        let path = std::env::temp_dir().join(name);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Open `path` in `$VISUAL` or `$EDITOR` (falling back to vi) and wait for
+/// it to exit. The editor is run through the shell so it can carry
+/// arguments, e.g. `code --wait`.
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("Editor '{}' exited with {}", editor, status));
+    }
+    Ok(())
+}
+
+/// Parse and check an edited record. The ID and commit identify where the
+/// record is stored, so they can't change here. Unknown fields are rejected
+/// too: a typo like `outcom` would otherwise be dropped without a word.
+fn parse_edit(original: &ArfRecord, content: &str) -> Result<ArfRecord> {
+    let table: toml::Table =
+        toml::from_str(content).map_err(|e| anyhow!("Invalid record: {}", e.message()))?;
+    // Lint warnings are advice; schema problems, unknown fields included, are not
+    let problems: Vec<String> = validate::check_table(&table, None)
+        .into_iter()
+        .filter(|p| p.rule.is_none())
+        .map(|p| p.to_string())
+        .collect();
+    if !problems.is_empty() {
+        return Err(anyhow!("Invalid record: {}", problems.join("; ")));
+    }
+
+    let record =
+        ArfRecord::from_toml(content).map_err(|e| anyhow!("Invalid record: {}", e.message()))?;
+    record.validate()?;
+
+    if record.id != original.id {
+        return Err(anyhow!("'id' can't be changed"));
+    }
+    if record.commit != original.commit {
+        return Err(anyhow!(
+            "'commit' can't be changed (use 'arf rewrite' to move records)"
+        ));
+    }
+    Ok(record)
+}
+
+fn cmd_retract(repo: &Path, reference: &str, reason: String) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let stored = store.find(reference)?;
+
+    if let Some(ref retraction) = stored.record.retracted {
+        return Err(anyhow!(
+            "Record {} is already retracted: {}",
+            stored.record.name(),
+            retraction
+        ));
+    }
+
+    let mut record = stored.record.clone();
+    record.retracted = Some(Retraction::new(reason.clone()));
+    let message = format!("Retract: {}\n\n{}", record.what, reason);
+    let updated = store.update(&stored, record, &message)?;
+
+    println!("✓ Retracted: {}", updated.record.what);
+    println!("  Reason: {}", reason);
+
+    Ok(())
+}
+
+fn cmd_log(repo: &Path, query: &Query, format: Format) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+
+    let records = store.query(query)?;
+
+    if format != Format::Text {
+        let mut commits: HashMap<String, Option<git::CommitInfo>> = HashMap::new();
+        let items: Vec<LogItem> = records
+            .into_iter()
+            .map(|stored| {
+                let commit = stored.record.commit.as_ref().and_then(|sha| {
+                    commits
+                        .entry(sha.clone())
+                        .or_insert_with(|| git::commit_info(repo, sha).ok())
+                        .clone()
+                });
+                LogItem {
+                    commit,
+                    entry: RecordEntry::from(stored),
+                }
+            })
+            .collect();
+        print!("{}", schema::render(format, "log", &items)?);
+        return Ok(());
+    }
+
+    if records.is_empty() {
+        match query.commit {
+            _ if query.has_filters() => println!("No records match the filters."),
+            Some(ref c) => println!("No records for commit {}", git::short_sha(c)),
+            None => println!("No ARF records found."),
+        }
+        return Ok(());
+    }
+
+    println!("ARF Records ({}):\n", records.len());
+
+    for stored in records {
+        let record = stored.record;
+
+        println!("commit {}", record.short_commit());
+        println!("id: {}", record.name());
         println!("what: {}", record.what);
         println!("why: {}", record.why);
         if let Some(ref how) = record.how {
@@ -455,6 +1262,18 @@ fn cmd_log(commit: Option<String>, limit: usize) -> Result<()> {
         if let Some(ref backup) = record.backup {
             println!("backup: {}", backup);
         }
+        if let Some(ref outcome) = record.outcome {
+            println!("outcome: {}", outcome);
+        }
+        if let Some(ref retraction) = record.retracted {
+            println!("retracted: {}", retraction);
+        }
+        if !record.context.is_empty() {
+            println!("context:");
+            for line in record.context_lines() {
+                println!("  {}", line);
+            }
+        }
         println!("time: {}", record.timestamp);
         println!();
     }
@@ -462,11 +1281,74 @@ fn cmd_log(commit: Option<String>, limit: usize) -> Result<()> {
     Ok(())
 }
 
-fn cmd_sync(push: bool, pull: bool) -> Result<()> {
-    if !Path::new(".arf").exists() {
-        return Err(anyhow!("ARF not initialized. Run 'arf init' first."));
+fn cmd_search(repo: &Path, query: &str, limit: usize, rebuild: bool) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+
+    let mut index = if rebuild {
+        Index::default()
+    } else {
+        Index::load(repo)?
+    };
+    let indexed_tip = index.tip().map(String::from);
+    index.update(&store)?;
+    if rebuild || index.tip() != indexed_tip.as_deref() {
+        index.save(repo)?;
     }
 
+    let hits = index.search(query, limit);
+    if hits.is_empty() {
+        println!("No records match '{}' ({} indexed)", query, index.len());
+        return Ok(());
+    }
+
+    let color = std::io::stdout().is_terminal();
+    println!("Results ({} of {} records):\n", hits.len(), index.len());
+
+    for hit in hits {
+        let doc = hit.doc;
+        let commit = match doc.commit {
+            Some(ref sha) => git::run(repo, &["log", "-1", "--format=%h %s", sha])
+                .unwrap_or_else(|_| format!("{} (missing)", git::short_sha(sha))),
+            None => "none".to_string(),
+        };
+
+        println!("{}  {:.2}", doc.id, hit.score);
+        println!("  commit {}", commit);
+        println!("  what: {}", doc.what);
+
+        let snippet: String = search::snippet(&doc.text, query, 100)
+            .into_iter()
+            .map(|(text, hit)| match (hit, color) {
+                (true, true) => text.bold().yellow().to_string(),
+                (true, false) => format!("**{}**", text),
+                (false, _) => text,
+            })
+            .collect();
+        println!("  {}", snippet);
+        println!();
+    }
+
+    Ok(())
+}
+
+fn cmd_sync(
+    repo: &Path,
+    push: bool,
+    pull: bool,
+    prefer: Option<Prefer>,
+    remote: Option<String>,
+    all: bool,
+) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let config = store.config();
+    // An explicit --remote syncs with that remote alone
+    let mirrors = if remote.is_some() {
+        Vec::new()
+    } else {
+        config.mirrors.clone()
+    };
+    let remote = remote.unwrap_or_else(|| config.remote.clone());
+
     // Default to both if neither specified
     let (do_pull, do_push) = if !push && !pull {
         (true, true)
@@ -475,133 +1357,208 @@ fn cmd_sync(push: bool, pull: bool) -> Result<()> {
     };
 
     if do_pull {
-        println!("Pulling ARF records...");
-        let output = Command::new("git")
-            .args(["pull", "origin", ARF_BRANCH])
-            .current_dir(".arf")
-            .output()?;
-
-        if output.status.success() {
-            println!("✓ Pulled");
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("couldn't find remote ref") {
-                println!("  No remote ARF branch yet");
-            } else {
-                println!("  Pull failed: {}", stderr.trim());
+        let mut sources = vec![remote.clone()];
+        if all {
+            sources.extend(sync::remotes(&store)?.into_iter().filter(|r| *r != remote));
+        }
+
+        let mut conflicted = false;
+        for source in &sources {
+            println!("Pulling ARF records from {}...", source);
+            let pulled = match store.storage() {
+                Storage::Notes => sync::pull_notes(&store, source, prefer)?,
+                Storage::Branch => sync::pull_branch(&store, source, prefer)?,
+            };
+            match pulled {
+                Pulled::NoRemote => println!("  No ARF records on {} yet", source),
+                Pulled::UpToDate => println!("✓ Already up to date"),
+                Pulled::FastForward => println!("✓ Pulled"),
+                Pulled::Merged { taken, resolved } => {
+                    println!("✓ Merged {} change(s) from {}", taken, source);
+                    if resolved > 0 {
+                        let side = prefer.unwrap_or(Prefer::Local);
+                        println!(
+                            "  Kept the {} version of {} record(s) changed on both sides",
+                            side, resolved
+                        );
+                    }
+                }
+                Pulled::Conflicts(conflicts) => {
+                    report_conflicts(source, &conflicts);
+                    conflicted = true;
+                }
             }
         }
+        if conflicted {
+            std::process::exit(1);
+        }
     }
 
     if do_push {
-        println!("Pushing ARF records...");
-        let output = Command::new("git")
-            .args(["push", "-u", "origin", ARF_BRANCH])
-            .current_dir(".arf")
-            .output()?;
-
-        if output.status.success() {
-            println!("✓ Pushed");
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            println!("  Push failed: {}", stderr.trim());
+        // Keep going past a failing mirror, but don't let it pass for success
+        let targets: Vec<&String> = std::iter::once(&remote).chain(&mirrors).collect();
+        let mut failed: Vec<&String> = Vec::new();
+        for &target in &targets {
+            println!("Pushing ARF records to {}...", target);
+            match sync::push(&store, target) {
+                Ok(Pushed::Done) => println!("✓ Pushed"),
+                Ok(Pushed::Rejected) => {
+                    println!(
+                        "  Push rejected: {} has records you don't have yet. \
+                         Run 'arf sync --remote {}' to merge them, then push again.",
+                        target, target
+                    );
+                    failed.push(target);
+                }
+                Err(e) => {
+                    println!("  {}", e);
+                    failed.push(target);
+                }
+            }
+        }
+        if !failed.is_empty() {
+            if targets.len() > 1 {
+                let names: Vec<&str> = failed.iter().map(|t| t.as_str()).collect();
+                println!(
+                    "Pushed to {} of {} remote(s); failed: {}",
+                    targets.len() - failed.len(),
+                    targets.len(),
+                    names.join(", ")
+                );
+            }
+            std::process::exit(1);
         }
     }
 
     Ok(())
 }
 
-fn cmd_graph(limit: usize) -> Result<()> {
-    // Get git log
-    let output = Command::new("git")
-        .args(["log", "--oneline", "--no-decorate", &format!("-{}", limit)])
-        .output()?;
+/// Explain records changed on both sides and how to settle them.
+fn report_conflicts(remote: &str, conflicts: &[Conflict]) {
+    println!(
+        "  Pull stopped: {} record(s) changed both here and on {}. Nothing was merged.",
+        conflicts.len(),
+        remote
+    );
+    println!();
 
-    if !output.status.success() {
-        return Err(anyhow!("Failed to get git log"));
+    let summary = |content: &Option<String>| match content {
+        None => "deleted".to_string(),
+        Some(content) => match ArfRecord::from_toml(content) {
+            Ok(record) => {
+                let mut parts = vec![format!("what: {}", record.what)];
+                if let Some(ref outcome) = record.outcome {
+                    parts.push(format!("outcome: {}", outcome));
+                }
+                if record.retracted.is_some() {
+                    parts.push("retracted".to_string());
+                }
+                parts.join(", ")
+            }
+            Err(_) => "unparseable".to_string(),
+        },
+    };
+    for conflict in conflicts {
+        println!("  {}  {}", conflict.record_name(), conflict.path);
+        println!("    local:  {}", summary(&conflict.local));
+        println!("    remote: {}", summary(&conflict.remote));
+    }
+
+    println!();
+    println!("Keep one side of every conflicting record and merge the rest:");
+    println!("  arf sync --prefer local     # keep your versions");
+    println!("  arf sync --prefer remote    # take {}'s versions", remote);
+    println!("Then adjust a record if needed with: arf edit <id>");
+}
+
+fn cmd_graph(repo: &Path, limit: usize, format: Format) -> Result<()> {
+    if format != Format::Text {
+        let store = RecordStore::open(repo)?;
+        let items: Vec<GraphItem> = git::commits(repo, &[&format!("-{}", limit)])?
+            .into_iter()
+            .map(|commit| {
+                let records: Vec<RecordEntry> = store
+                    .load_resolved(&commit.sha)
+                    .into_iter()
+                    .map(RecordEntry::from)
+                    .collect();
+                let embedded = if records.is_empty() {
+                    embedded_records(repo, &commit.sha)
+                } else {
+                    Vec::new()
+                };
+                GraphItem {
+                    records,
+                    embedded,
+                    commit,
+                }
+            })
+            .collect();
+        print!("{}", schema::render(format, "graph", &items)?);
+        return Ok(());
     }
 
-    let log = String::from_utf8_lossy(&output.stdout);
-    let commits: Vec<&str> = log.lines().collect();
+    let commits = git::log(repo, limit)?;
 
     if commits.is_empty() {
         println!("No commits found.");
         return Ok(());
     }
 
-    let records_dir = Path::new(".arf/records");
-    let has_arf = records_dir.exists();
+    let store = RecordStore::open(repo)?;
+    let has_arf = store.is_initialized();
 
     println!("Git + ARF History:\n");
 
-    for (i, line) in commits.iter().enumerate() {
-        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-        let (sha, msg) = if parts.len() == 2 {
-            (parts[0], parts[1])
-        } else {
-            (parts[0], "")
-        };
-
+    for (i, commit) in commits.iter().enumerate() {
         // Graph connector
         let is_last = i == commits.len() - 1;
         let connector = if is_last { "└" } else { "├" };
         let continuation = if is_last { " " } else { "│" };
 
         // Print commit line
-        println!("{}─● {} {}", connector, sha, msg);
-
-        // Check for ARF records for this commit
-        if has_arf {
-            // Try to find matching records dir (git log shows 7 chars, records use 8)
-            let commit_records_dir = if let Ok(entries) = std::fs::read_dir(records_dir) {
-                entries
-                    .filter_map(|e| e.ok())
-                    .find(|e| e.file_name().to_string_lossy().starts_with(sha))
-                    .map(|e| e.path())
-            } else {
-                None
-            };
-
-            if let Some(commit_records_dir) = commit_records_dir {
-                if let Ok(entries) = std::fs::read_dir(&commit_records_dir) {
-                    let mut records: Vec<ArfRecord> = Vec::new();
-
-                    for entry in entries.filter_map(|e| e.ok()) {
-                        let path = entry.path();
-                        if path.extension().is_some_and(|e| e == "toml") {
-                            if let Ok(content) = std::fs::read_to_string(&path) {
-                                if let Ok(record) = toml::from_str::<ArfRecord>(&content) {
-                                    records.push(record);
-                                }
-                            }
-                        }
-                    }
+        println!("{}─● {} {}", connector, commit.short_sha, commit.subject);
 
-                    // Sort by timestamp
-                    records.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-                    for (j, record) in records.iter().enumerate() {
-                        let is_last_record = j == records.len() - 1;
-                        let rec_connector = if is_last_record { "└" } else { "├" };
+        // Fall back to reasoning embedded in the commit message
+        let mut records: Vec<ArfRecord> = store
+            .load_resolved(&commit.sha)
+            .into_iter()
+            .map(|stored| stored.record)
+            .collect();
+        let embedded = records.is_empty();
+        if embedded {
+            records = embedded_records(repo, &commit.sha);
+        }
 
-                        println!("{}  {}─ what: {}", continuation, rec_connector, record.what);
-                        println!(
-                            "{}  {}   why: {}",
-                            continuation,
-                            if is_last_record { " " } else { "│" },
-                            record.why
-                        );
+        for (j, record) in records.iter().enumerate() {
+            let is_last_record = j == records.len() - 1;
+            let rec_connector = if is_last_record { "└" } else { "├" };
 
-                        if let Some(ref how) = record.how {
-                            println!(
-                                "{}  {}   how: {}",
-                                continuation,
-                                if is_last_record { " " } else { "│" },
-                                how
-                            );
-                        }
-                    }
-                }
+            let retracted = if record.retracted.is_some() {
+                " (retracted)"
+            } else if embedded {
+                " (from commit message)"
+            } else {
+                ""
+            };
+            println!(
+                "{}  {}─ what: {}{}",
+                continuation, rec_connector, record.what, retracted
+            );
+            println!(
+                "{}  {}   why: {}",
+                continuation,
+                if is_last_record { " " } else { "│" },
+                record.why
+            );
+
+            if let Some(ref how) = record.how {
+                println!(
+                    "{}  {}   how: {}",
+                    continuation,
+                    if is_last_record { " " } else { "│" },
+                    how
+                );
             }
         }
     }
@@ -613,85 +1570,209 @@ fn cmd_graph(limit: usize) -> Result<()> {
     Ok(())
 }
 
-fn cmd_diff(commit: Option<String>, full: bool) -> Result<()> {
-    // Get the commit SHA (default to HEAD)
-    let sha = match commit {
-        Some(c) => c,
-        None => {
-            let output = Command::new("git").args(["rev-parse", "HEAD"]).output()?;
-            if !output.status.success() {
-                return Err(anyhow!("Failed to get HEAD"));
+/// Records embedded in a commit's message, if any.
+fn embedded_records(repo: &Path, sha: &str) -> Vec<ArfRecord> {
+    let (Ok(message), Ok(info)) = (git::message(repo, sha), git::commit_info(repo, sha)) else {
+        return Vec::new();
+    };
+    embedded::extract(sha, &message, &info.author_date)
+}
+
+fn cmd_check(repo: &Path, range: &str, rules: Rules) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    // Rules on the command line replace the configured ones
+    let rules = if rules.is_empty() {
+        Rules::from_config(store.config())
+    } else {
+        rules
+    };
+
+    let args: Vec<&str> = range.split_whitespace().collect();
+    let result = check::check(&store, &args, &rules)?;
+
+    if result.violations.is_empty() {
+        println!(
+            "✓ {} of {} commit(s) need reasoning, and all have it",
+            result.required, result.commits
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Missing reasoning ({} of {} commit(s) that need it):",
+        result.violations.len(),
+        result.required
+    );
+    for violation in &result.violations {
+        println!(
+            "  {} {}",
+            violation.commit.short_sha, violation.commit.subject
+        );
+        println!("      rule: {}", violation.rule);
+    }
+    println!();
+    println!("Add it with: arf record -c <commit> --what \"...\" --why \"...\"");
+    std::process::exit(1);
+}
+
+fn cmd_coverage(
+    repo: &Path,
+    range: &str,
+    min_coverage: Option<f64>,
+    author: Option<&str>,
+    no_merges: bool,
+    format: Format,
+) -> Result<()> {
+    if let Some(min) = min_coverage {
+        if !(0.0..=100.0).contains(&min) {
+            return Err(anyhow!("--min-coverage must be between 0 and 100"));
+        }
+    }
+
+    let store = RecordStore::open(repo)?;
+    let author_arg = author.map(|a| format!("--author={}", a));
+    let mut args: Vec<&str> = Vec::new();
+    if no_merges {
+        args.push("--no-merges");
+    }
+    args.extend(author_arg.as_deref());
+    args.extend(range.split_whitespace());
+    let coverage = Coverage::compute(&store, &args)?;
+
+    if format != Format::Text {
+        let items: Vec<CoverageItem> = coverage
+            .commits
+            .iter()
+            .map(|c| CoverageItem {
+                commit: c.commit.clone(),
+                covered: c.is_covered(),
+                records: c.records,
+            })
+            .collect();
+        print!("{}", schema::render(format, "coverage", &items)?);
+    } else {
+        println!(
+            "Coverage for {}: {} of {} commit(s) ({:.1}%)",
+            range,
+            coverage.covered(),
+            coverage.commits.len(),
+            coverage.percent()
+        );
+
+        let authors = coverage.by_author();
+        if !authors.is_empty() {
+            println!();
+            println!("By author:");
+            let width = authors
+                .iter()
+                .map(|a| a.author.name.chars().count() + a.author.email.len() + 3)
+                .max()
+                .unwrap_or(0);
+            for a in &authors {
+                let who = format!("{} <{}>", a.author.name, a.author.email);
+                println!(
+                    "  {:<width$}  {:>4}/{:<4} {:5.1}%",
+                    who,
+                    a.covered,
+                    a.commits,
+                    a.percent(),
+                    width = width
+                );
             }
-            String::from_utf8_lossy(&output.stdout).trim().to_string()
         }
-    };
 
-    // Get commit info
-    let output = Command::new("git")
-        .args(["log", "-1", "--oneline", &sha])
-        .output()?;
+        let missing: Vec<_> = coverage.missing().collect();
+        if !missing.is_empty() {
+            println!();
+            println!("Missing reasoning ({}):", missing.len());
+            for commit in missing {
+                println!(
+                    "  {} {} ({})",
+                    commit.short_sha, commit.subject, commit.author.name
+                );
+            }
+        }
+    }
 
-    if !output.status.success() {
-        return Err(anyhow!("Commit not found: {}", sha));
+    if let Some(min) = min_coverage {
+        if coverage.percent() < min {
+            eprintln!(
+                "Coverage {:.1}% is below the minimum of {}%",
+                coverage.percent(),
+                min
+            );
+            std::process::exit(1);
+        }
     }
 
-    let commit_line = String::from_utf8_lossy(&output.stdout);
-    let commit_line = commit_line.trim();
+    Ok(())
+}
 
-    // Print ARF context first
-    let records_dir = Path::new(".arf/records");
-    let short_sha = &sha[..8.min(sha.len())];
+fn cmd_diff(repo: &Path, commit: Option<String>, full: bool, format: Format) -> Result<()> {
+    let sha = git::resolve_commit(repo, commit.as_deref().unwrap_or("HEAD"))?;
+
+    if format != Format::Text {
+        let store = RecordStore::open(repo)?;
+        let patch = if full {
+            let output = git::output(repo, &["show", "--format=", &sha])?;
+            Some(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            None
+        };
+        let item = DiffItem {
+            commit: git::commit_info(repo, &sha)?,
+            records: store
+                .load_resolved(&sha)
+                .into_iter()
+                .map(RecordEntry::from)
+                .collect(),
+            files: git::file_stats(repo, &sha)?,
+            patch,
+        };
+        print!("{}", schema::render(format, "diff", &[item])?);
+        return Ok(());
+    }
+
+    // Get commit info
+    let commit_line = git::run(repo, &["log", "-1", "--oneline", &sha])?;
 
     println!("═══════════════════════════════════════════════════════════════");
     println!("Commit: {}", commit_line);
     println!("═══════════════════════════════════════════════════════════════");
 
-    if records_dir.exists() {
-        // Find matching records dir (match either direction for flexibility)
-        let commit_records_dir = std::fs::read_dir(records_dir).ok().and_then(|entries| {
-            entries
-                .filter_map(|e| e.ok())
-                .find(|e| {
-                    let dir_name = e.file_name().to_string_lossy().to_string();
-                    dir_name.starts_with(short_sha) || short_sha.starts_with(&dir_name)
-                })
-                .map(|e| e.path())
-        });
-
-        if let Some(dir) = commit_records_dir {
-            if let Ok(entries) = std::fs::read_dir(&dir) {
-                let mut records: Vec<ArfRecord> = Vec::new();
-
-                for entry in entries.filter_map(|e| e.ok()) {
-                    let path = entry.path();
-                    if path.extension().is_some_and(|e| e == "toml") {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            if let Ok(record) = toml::from_str::<ArfRecord>(&content) {
-                                records.push(record);
-                            }
-                        }
-                    }
-                }
+    // Print ARF context first
+    let store = RecordStore::open(repo)?;
+    if store.is_initialized() {
+        let records = store.load_resolved(&sha);
 
-                records.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-                if !records.is_empty() {
-                    println!();
-                    println!("REASONING:");
-                    for record in &records {
-                        println!("  what: {}", record.what);
-                        println!("  why:  {}", record.why);
-                        if let Some(ref how) = record.how {
-                            println!("  how:  {}", how);
-                        }
-                        println!();
-                    }
-                }
-            }
-        } else {
+        if records.is_empty() {
             println!();
             println!("(no ARF record for this commit)");
             println!();
+        } else {
+            println!();
+            println!("REASONING:");
+            for stored in &records {
+                let record = &stored.record;
+                println!("  what: {}", record.what);
+                println!("  why:  {}", record.why);
+                if let Some(ref how) = record.how {
+                    println!("  how:  {}", how);
+                }
+                if let Some(ref outcome) = record.outcome {
+                    println!("  outcome: {}", outcome);
+                }
+                if let Some(ref retraction) = record.retracted {
+                    println!("  retracted: {}", retraction);
+                }
+                if !record.context.is_empty() {
+                    println!("  context:");
+                    for line in record.context_lines() {
+                        println!("    {}", line);
+                    }
+                }
+                println!();
+            }
         }
     }
 
@@ -706,7 +1787,7 @@ fn cmd_diff(commit: Option<String>, full: bool) -> Result<()> {
         vec!["show", "--stat", "--format=", &sha]
     };
 
-    let diff_output = Command::new("git").args(&diff_args).output()?;
+    let diff_output = git::output(repo, &diff_args)?;
 
     if diff_output.status.success() {
         print!("{}", String::from_utf8_lossy(&diff_output.stdout));
@@ -715,435 +1796,527 @@ fn cmd_diff(commit: Option<String>, full: bool) -> Result<()> {
     Ok(())
 }
 
-// TUI types and implementation
+fn cmd_rewrite(repo: &Path, kind: Option<String>, copy: bool) -> Result<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let pairs = hooks::parse_rewrite_pairs(&input)?;
 
-#[derive(Debug)]
-struct CommitInfo {
-    sha: String,
-    short_sha: String,
-    message: String,
-    records: Vec<ArfRecord>,
-}
+    let store = RecordStore::open(repo)?;
+    if !store.is_initialized() || pairs.is_empty() {
+        return Ok(());
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum DiffMode {
-    Hidden,
-    Stat,
-    Full,
-}
+    let count = store.rewrite(&pairs, copy)?;
+    if count > 0 {
+        let verb = if copy { "Copied" } else { "Moved" };
+        match kind {
+            Some(kind) => println!("arf: {} {} record(s) after {}", verb, count, kind),
+            None => println!("arf: {} {} record(s)", verb, count),
+        }
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Focus {
-    Commits,
-    Diff,
+    Ok(())
 }
 
-struct App {
-    commits: Vec<CommitInfo>,
-    list_state: ListState,
-    diff_mode: DiffMode,
-    diff_lines: Vec<DiffLine>,
-    diff_scroll: usize,
-    focus: Focus,
-    should_quit: bool,
-}
+fn cmd_export(
+    repo: &Path,
+    range: Option<String>,
+    stream: bool,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let query = Query {
+        range,
+        ..Default::default()
+    };
+    let mut records: Vec<ArfRecord> = store
+        .query(&query)?
+        .into_iter()
+        .map(|stored| stored.record)
+        .collect();
+    records.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+    if stream {
+        let content = ArfRecord::to_toml_stream(&records)?;
+        match output {
+            Some(path) if path != Path::new("-") => {
+                std::fs::write(&path, content)
+                    .map_err(|e| anyhow!("Can't write {}: {}", path.display(), e))?;
+                println!(
+                    "✓ Exported {} record(s) to {}",
+                    records.len(),
+                    path.display()
+                );
+            }
+            _ => print!("{}", content),
+        }
+        return Ok(());
+    }
+
+    // One file per record, laid out like the arf branch
+    let dir = output.ok_or_else(|| anyhow!("Without --stream, --output must name a directory"))?;
+    for record in &records {
+        let commit_dir = dir.join(record.commit.as_deref().unwrap_or("unattached"));
+        std::fs::create_dir_all(&commit_dir)?;
+        std::fs::write(
+            commit_dir.join(format!("{}.toml", record.name())),
+            record.to_toml()?,
+        )?;
+    }
+    println!(
+        "✓ Exported {} record(s) to {}",
+        records.len(),
+        dir.display()
+    );
 
-#[derive(Debug, Clone)]
-struct DiffLine {
-    content: String,
-    style: Style,
+    Ok(())
 }
 
-impl App {
-    fn new(commits: Vec<CommitInfo>) -> Self {
-        let mut list_state = ListState::default();
-        if !commits.is_empty() {
-            list_state.select(Some(0));
-        }
-        Self {
-            commits,
-            list_state,
-            diff_mode: DiffMode::Stat,
-            diff_lines: Vec::new(),
-            diff_scroll: 0,
-            focus: Focus::Commits,
-            should_quit: false,
-        }
+fn cmd_import(
+    repo: &Path,
+    files: &[PathBuf],
+    from_commits: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let (imported, skipped, source) = match from_commits {
+        Some(range) => embedded_imports(repo, &store, range)?,
+        None => file_imports(repo, &store, files)?,
+    };
+
+    if imported.is_empty() {
+        println!(
+            "No new records in {}{}",
+            source,
+            if skipped > 0 {
+                format!(" ({} already imported)", skipped)
+            } else {
+                String::new()
+            }
+        );
+        return Ok(());
     }
 
-    fn selected_commit(&self) -> Option<&CommitInfo> {
-        self.list_state.selected().and_then(|i| self.commits.get(i))
+    if dry_run {
+        println!();
+        println!("Would import {} record(s) (dry run)", imported.len());
+        return Ok(());
     }
 
-    fn next(&mut self) {
-        match self.focus {
-            Focus::Commits => {
-                if self.commits.is_empty() {
-                    return;
-                }
-                let i = match self.list_state.selected() {
-                    Some(i) => (i + 1) % self.commits.len(),
-                    None => 0,
-                };
-                self.list_state.select(Some(i));
-                self.diff_scroll = 0;
-                self.update_diff();
-            }
-            Focus::Diff => {
-                if self.diff_scroll < self.diff_lines.len().saturating_sub(1) {
-                    self.diff_scroll += 1;
-                }
-            }
-        }
+    let count = imported.len();
+    store.write_all(
+        imported,
+        &format!("Import {} record(s) from {}", count, source),
+    )?;
+    println!();
+    println!("✓ Imported {} record(s) from {}", count, source);
+    if skipped > 0 {
+        println!("  Skipped {} already imported", skipped);
     }
 
-    fn previous(&mut self) {
-        match self.focus {
-            Focus::Commits => {
-                if self.commits.is_empty() {
-                    return;
-                }
-                let i = match self.list_state.selected() {
-                    Some(i) => {
-                        if i == 0 {
-                            self.commits.len() - 1
-                        } else {
-                            i - 1
-                        }
-                    }
-                    None => 0,
-                };
-                self.list_state.select(Some(i));
-                self.diff_scroll = 0;
-                self.update_diff();
-            }
-            Focus::Diff => {
-                self.diff_scroll = self.diff_scroll.saturating_sub(1);
+    Ok(())
+}
+
+/// Records embedded in the messages of commits in `range`, with the number
+/// already imported and a description of where they came from.
+fn embedded_imports(
+    repo: &Path,
+    store: &RecordStore,
+    range: &str,
+) -> Result<(Vec<ArfRecord>, usize, String)> {
+    let args: Vec<&str> = range.split_whitespace().collect();
+    let commits = git::commits(repo, &args)?;
+
+    let mut imported = Vec::new();
+    let mut skipped = 0;
+    for commit in &commits {
+        let message = git::message(repo, &commit.sha)?;
+        let existing = store.load_resolved(&commit.sha);
+
+        for mut record in embedded::extract(&commit.sha, &message, &commit.author_date) {
+            // Importing twice shouldn't duplicate anything
+            let known = existing
+                .iter()
+                .any(|s| s.record.what == record.what && s.record.why == record.why);
+            if known {
+                skipped += 1;
+                continue;
             }
+            record.commit = Some(commit.sha.clone());
+            println!("  {} {}", commit.short_sha, record.what);
+            imported.push(record);
         }
     }
 
-    fn toggle_focus(&mut self) {
-        if self.diff_mode != DiffMode::Hidden {
-            self.focus = match self.focus {
-                Focus::Commits => Focus::Diff,
-                Focus::Diff => Focus::Commits,
-            };
-        }
-    }
+    let source = format!("commit messages of {} commit(s)", commits.len());
+    Ok((imported, skipped, source))
+}
 
-    fn toggle_diff(&mut self) {
-        self.diff_mode = match self.diff_mode {
-            DiffMode::Hidden => DiffMode::Stat,
-            DiffMode::Stat => DiffMode::Full,
-            DiffMode::Full => DiffMode::Hidden,
+/// Records from record files (single records or TOML streams), keeping their
+/// IDs. Each goes to the commit its `commit` field names.
+fn file_imports(
+    repo: &Path,
+    store: &RecordStore,
+    files: &[PathBuf],
+) -> Result<(Vec<ArfRecord>, usize, String)> {
+    let known: HashSet<String> = store.list()?.iter().map(|s| s.record.name()).collect();
+
+    let mut imported: Vec<ArfRecord> = Vec::new();
+    let mut skipped = 0;
+    for path in files {
+        let content = if path == Path::new("-") {
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            content
+        } else {
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("Can't read {}: {}", path.display(), e))?
         };
-        if self.diff_mode == DiffMode::Hidden {
-            self.focus = Focus::Commits;
+        let records = ArfRecord::from_toml_file(&content)
+            .map_err(|e| anyhow!("Invalid record file {}: {}", path.display(), e.message()))?;
+
+        for mut record in records {
+            record.validate()?;
+            if known.contains(&record.name()) || imported.iter().any(|r| r.name() == record.name())
+            {
+                skipped += 1;
+                continue;
+            }
+            let Some(commit) = record.commit.as_deref() else {
+                return Err(anyhow!(
+                    "Record {} has no commit to attach to",
+                    record.name()
+                ));
+            };
+            let sha = git::resolve_commit(repo, commit).map_err(|_| {
+                anyhow!(
+                    "Record {} is for commit {}, which is not in this repository",
+                    record.name(),
+                    commit
+                )
+            })?;
+            println!("  {} {}", git::short_sha(&sha), record.what);
+            record.commit = Some(sha);
+            imported.push(record);
         }
-        self.diff_scroll = 0;
-        self.update_diff();
     }
 
-    fn page_down(&mut self) {
-        if self.focus == Focus::Diff {
-            self.diff_scroll = (self.diff_scroll + 10).min(self.diff_lines.len().saturating_sub(1));
-        }
-    }
+    let source = match files {
+        [path] if path == Path::new("-") => "stdin".to_string(),
+        [path] => path.display().to_string(),
+        _ => format!("{} files", files.len()),
+    };
+    Ok((imported, skipped, source))
+}
 
-    fn page_up(&mut self) {
-        if self.focus == Focus::Diff {
-            self.diff_scroll = self.diff_scroll.saturating_sub(10);
-        }
+fn print_report(report: &Report) {
+    let entry = report
+        .entry
+        .map(|n| format!(" [record {}]", n))
+        .unwrap_or_default();
+    let what = report
+        .what
+        .as_deref()
+        .map(|w| format!(" ({})", w))
+        .unwrap_or_default();
+    println!("{}{}{}", report.source, entry, what);
+    for problem in &report.problems {
+        println!("  {:<7} {}", problem.severity, problem);
     }
+    println!();
+}
 
-    fn update_diff(&mut self) {
-        self.diff_lines.clear();
+fn cmd_validate(repo: &Path, paths: &[PathBuf], strict: bool, no_lint: bool) -> Result<()> {
+    let lint = Lint::from_config(&Config::load(repo).unwrap_or_default());
+    let lint = (!no_lint).then_some(&lint);
 
-        if self.diff_mode == DiffMode::Hidden {
-            return;
+    let reports = if paths.is_empty() {
+        let store = RecordStore::open(repo)?;
+        if !store.is_initialized() {
+            return Err(anyhow!("ARF not initialized. Run 'arf init' first."));
         }
+        validate::check_store(&store, lint)?
+    } else {
+        let repo = git::is_repo(repo).then_some(repo);
+        let mut reports = Vec::new();
+        for path in paths {
+            let (source, content) = if path == Path::new("-") {
+                let mut content = String::new();
+                std::io::stdin().read_to_string(&mut content)?;
+                ("stdin".to_string(), content)
+            } else {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow!("Can't read {}: {}", path.display(), e))?;
+                (path.display().to_string(), content)
+            };
+            reports.extend(validate::check_file(&source, &content, repo, lint));
+        }
+        reports
+    };
 
-        let Some(commit) = self.selected_commit() else {
-            return;
-        };
+    let count = reports.len();
+    let failing: Vec<&Report> = reports.iter().filter(|r| !r.problems.is_empty()).collect();
 
-        let args = if self.diff_mode == DiffMode::Full {
-            vec!["show", "--format=", &commit.sha]
-        } else {
-            vec!["show", "--stat", "--format=", &commit.sha]
-        };
+    for report in &failing {
+        print_report(report);
+    }
 
-        let output = Command::new("git").args(&args).output();
-        let content = match output {
-            Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
-            _ => "Failed to get diff".to_string(),
-        };
+    let errors = reports.iter().filter(|r| r.has_errors()).count();
+    let warnings = failing.len() - errors;
+    if failing.is_empty() {
+        println!("✓ {} record(s) valid", count);
+        return Ok(());
+    }
+    println!(
+        "{} of {} record(s) invalid, {} with warnings",
+        errors, count, warnings
+    );
+    if errors > 0 || strict {
+        std::process::exit(1);
+    }
 
-        // Parse lines with syntax highlighting
-        for line in content.lines() {
-            let (style, display) = if line.starts_with('+') && !line.starts_with("+++") {
-                (Style::default().fg(Color::Green), line.to_string())
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                (Style::default().fg(Color::Red), line.to_string())
-            } else if line.starts_with("@@") {
-                (Style::default().fg(Color::Cyan), line.to_string())
-            } else if line.starts_with("diff ") || line.starts_with("index ") {
-                (Style::default().fg(Color::Yellow).bold(), line.to_string())
-            } else if line.starts_with("+++") || line.starts_with("---") {
-                (Style::default().fg(Color::Yellow), line.to_string())
-            } else {
-                (Style::default(), line.to_string())
-            };
+    Ok(())
+}
 
-            self.diff_lines.push(DiffLine {
-                content: display,
-                style,
-            });
+fn cmd_hooks_install(repo: &Path, names: &[String], force: bool) -> Result<()> {
+    for (hook, status) in hooks::install(repo, names, force)? {
+        match status {
+            HookStatus::Installed => println!("✓ Installed {} ({})", hook.name, hook.description),
+            HookStatus::Updated => println!("✓ Updated {} ({})", hook.name, hook.description),
+            _ => println!(
+                "  Skipped {}: existing hook not installed by arf (use --force to replace)",
+                hook.name
+            ),
         }
     }
+
+    Ok(())
 }
 
-fn cmd_browse() -> Result<()> {
-    // Get commits
-    let output = Command::new("git")
-        .args(["log", "--oneline", "--no-decorate", "-50"])
-        .output()?;
+fn cmd_hooks_uninstall(repo: &Path) -> Result<()> {
+    for (hook, status) in hooks::uninstall(repo)? {
+        match status {
+            HookStatus::Removed => println!("✓ Removed {}", hook.name),
+            HookStatus::Skipped => println!("  Kept {}: not installed by arf", hook.name),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
 
-    if !output.status.success() {
-        return Err(anyhow!("Failed to get git log"));
+fn scope(global: bool, shared: bool) -> Scope {
+    match (global, shared) {
+        (true, _) => Scope::Global,
+        (_, true) => Scope::Shared,
+        _ => Scope::Local,
     }
+}
 
-    let log = String::from_utf8_lossy(&output.stdout);
-    let records_dir = Path::new(".arf/records");
+fn config_key(name: &str) -> Result<&'static config::Key> {
+    config::find_key(name).ok_or_else(|| {
+        anyhow!(
+            "Unknown setting '{}'. See 'arf config list --all' for the settings arf reads.",
+            name
+        )
+    })
+}
 
-    let mut commits: Vec<CommitInfo> = Vec::new();
+fn cmd_config_get(repo: &Path, key: &str) -> Result<()> {
+    let config = Config::load(repo)?;
+    let values = config.get_all(key);
+    if values.is_empty() {
+        // Like git config: unset is a quiet failure
+        std::process::exit(1);
+    }
+    match config::find_key(key) {
+        Some(k) if !k.list => println!("{}", values[values.len() - 1]),
+        _ => values.iter().for_each(|v| println!("{}", v)),
+    }
+    Ok(())
+}
 
-    for line in log.lines() {
-        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-        let (sha, msg) = if parts.len() == 2 {
-            (parts[0], parts[1])
-        } else {
-            (parts[0], "")
-        };
+fn cmd_config_set(
+    repo: &Path,
+    key: &str,
+    values: &[String],
+    add: bool,
+    scope: Scope,
+) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    let key = config_key(key)?;
+    config::set(&store, scope, key, values, add)?;
+
+    let place = match scope {
+        Scope::Local => "this repository's git config".to_string(),
+        Scope::Global => "your global git config".to_string(),
+        Scope::Shared => format!("{} on '{}'", config::CONFIG_FILE, store.config().branch),
+    };
+    if values.is_empty() {
+        println!("✓ Unset {} in {}", key.name, place);
+    } else {
+        println!("✓ Set {} = {} in {}", key.name, values.join(", "), place);
+    }
 
-        let short_sha = sha.to_string();
-
-        // Get full SHA for diff
-        let full_sha = Command::new("git")
-            .args(["rev-parse", sha])
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .unwrap_or_else(|| sha.to_string());
-
-        // Find ARF records
-        let mut records = Vec::new();
-        if records_dir.exists() {
-            if let Ok(entries) = std::fs::read_dir(records_dir) {
-                for entry in entries.filter_map(|e| e.ok()) {
-                    let dir_name = entry.file_name().to_string_lossy().to_string();
-                    if dir_name.starts_with(&short_sha) || short_sha.starts_with(&dir_name) {
-                        if let Ok(record_entries) = std::fs::read_dir(entry.path()) {
-                            for record_entry in record_entries.filter_map(|e| e.ok()) {
-                                let path = record_entry.path();
-                                if path.extension().is_some_and(|e| e == "toml") {
-                                    if let Ok(content) = std::fs::read_to_string(&path) {
-                                        if let Ok(record) = toml::from_str::<ArfRecord>(&content) {
-                                            records.push(record);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    // A higher layer may still hide the new value
+    let config = Config::load(repo)?;
+    let written = match scope {
+        Scope::Shared => config::Source::File,
+        _ => config::Source::Git,
+    };
+    if let Some((effective, source)) = config.lookup(key.name) {
+        if source != written || (!add && effective != values) {
+            println!(
+                "  Note: {} from {} takes precedence: {}",
+                key.name,
+                source,
+                effective.join(", ")
+            );
         }
+    }
+    Ok(())
+}
 
-        commits.push(CommitInfo {
-            sha: full_sha,
-            short_sha,
-            message: msg.to_string(),
-            records,
-        });
+fn cmd_config_list(repo: &Path, all: bool, format: Format) -> Result<()> {
+    let config = Config::load(repo)?;
+    let mut items: Vec<ConfigItem> = config
+        .list()
+        .into_iter()
+        .map(|(key, values, source)| ConfigItem {
+            key,
+            values,
+            source: Some(source.to_string()),
+        })
+        .collect();
+    if all {
+        for key in config::KEYS {
+            if !items.iter().any(|i| i.key == key.name) {
+                items.push(ConfigItem {
+                    key: key.name.to_string(),
+                    values: Vec::new(),
+                    source: None,
+                });
+            }
+        }
+        items.sort_by(|a, b| a.key.cmp(&b.key));
     }
 
-    if commits.is_empty() {
-        println!("No commits found.");
+    if format != Format::Text {
+        print!("{}", schema::render(format, "config", &items)?);
         return Ok(());
     }
 
-    // Setup terminal
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-
-    let mut app = App::new(commits);
-    app.update_diff();
+    if items.is_empty() {
+        println!("No settings; arf is using its defaults.");
+        println!("  See them all with: arf config list --all");
+        return Ok(());
+    }
 
-    // Main loop
-    loop {
-        terminal.draw(|frame| ui(frame, &mut app))?;
-
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
-                    KeyCode::Down | KeyCode::Char('j') => app.next(),
-                    KeyCode::Up | KeyCode::Char('k') => app.previous(),
-                    KeyCode::Char('d') => app.toggle_diff(),
-                    KeyCode::Tab | KeyCode::Enter => app.toggle_focus(),
-                    KeyCode::PageDown | KeyCode::Char('f') => app.page_down(),
-                    KeyCode::PageUp | KeyCode::Char('b') => app.page_up(),
-                    _ => {}
-                }
+    let width = items.iter().map(|i| i.key.len()).max().unwrap_or(0);
+    for item in &items {
+        match item.source {
+            Some(ref source) => println!(
+                "{:width$}  {}  ({})",
+                item.key,
+                item.values.join(", "),
+                source,
+                width = width
+            ),
+            None => {
+                let help = config::find_key(&item.key).map_or("", |k| k.help);
+                println!("{:width$}  -  {}", item.key, help, width = width)
             }
         }
-
-        if app.should_quit {
-            break;
-        }
     }
-
-    // Restore terminal
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
-
     Ok(())
 }
 
-fn ui(frame: &mut Frame, app: &mut App) {
-    let has_diff = app.diff_mode != DiffMode::Hidden;
-
-    // Border styles based on focus
-    let focused_border = Style::default().fg(Color::Cyan);
-    let unfocused_border = Style::default();
+fn cmd_doctor(repo: &Path, fix: bool) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    if !store.is_initialized() {
+        return Err(anyhow!("ARF not initialized. Run 'arf init' first."));
+    }
 
-    // Main layout
-    let main_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(if has_diff {
-            vec![Constraint::Percentage(50), Constraint::Percentage(50)]
-        } else {
-            vec![Constraint::Percentage(100)]
-        })
-        .split(frame.area());
-
-    // Top section: commits + reasoning
-    let top_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
-        .split(main_chunks[0]);
-
-    // Commits list
-    let items: Vec<ListItem> = app
-        .commits
-        .iter()
-        .map(|c| {
-            let has_arf = if c.records.is_empty() { " " } else { "●" };
-            ListItem::new(format!("{} {} {}", has_arf, c.short_sha, c.message))
-        })
-        .collect();
+    let issues = doctor::check(&store)?;
 
-    let commits_border = if app.focus == Focus::Commits {
-        focused_border
-    } else {
-        unfocused_border
-    };
+    if issues.is_empty() {
+        println!("✓ No problems found");
+        return Ok(());
+    }
 
-    let commits_list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_style(commits_border)
-                .title(" Commits "),
-        )
-        .highlight_style(Style::default().bg(Color::DarkGray).bold())
-        .highlight_symbol("→ ");
+    if !fix {
+        println!("Problems ({}):\n", issues.len());
+        for issue in &issues {
+            let marker = if issue.is_fixable() {
+                "fixable"
+            } else {
+                "       "
+            };
+            println!("  [{}] {}", marker, issue);
+        }
+        println!();
+        if issues.iter().any(|i| i.is_fixable()) {
+            println!("Repair fixable problems with: arf doctor --fix");
+        }
+        std::process::exit(1);
+    }
 
-    frame.render_stateful_widget(commits_list, top_chunks[0], &mut app.list_state);
+    let fixed = doctor::fix(&store, &issues)?;
+    for issue in &fixed {
+        println!("✓ Fixed: {}", issue);
+    }
+    if fixed.is_empty() {
+        println!("Nothing could be fixed automatically.");
+    }
 
-    // Reasoning panel
-    let reasoning_text = if let Some(commit) = app.selected_commit() {
-        if commit.records.is_empty() {
-            "(no ARF record for this commit)".to_string()
-        } else {
-            commit
-                .records
-                .iter()
-                .map(|r| {
-                    let mut s = format!("what: {}\nwhy:  {}", r.what, r.why);
-                    if let Some(ref how) = r.how {
-                        s.push_str(&format!("\nhow:  {}", how));
-                    }
-                    if let Some(ref backup) = r.backup {
-                        s.push_str(&format!("\nback: {}", backup));
-                    }
-                    s
-                })
-                .collect::<Vec<_>>()
-                .join("\n\n---\n\n")
-        }
-    } else {
-        "No commit selected".to_string()
-    };
+    let remaining = doctor::check(&store)?;
+    if remaining.is_empty() {
+        println!("✓ No problems left");
+        return Ok(());
+    }
 
-    let reasoning = Paragraph::new(reasoning_text)
-        .block(Block::default().borders(Borders::ALL).title(" Reasoning "))
-        .wrap(Wrap { trim: false });
+    println!();
+    println!("Needs attention ({}):", remaining.len());
+    for issue in &remaining {
+        println!("  {}", issue);
+    }
+    std::process::exit(1);
+}
 
-    frame.render_widget(reasoning, top_chunks[1]);
+fn cmd_migrate(repo: &Path, dry_run: bool) -> Result<()> {
+    let store = RecordStore::open(repo)?;
 
-    // Diff panel (if visible)
-    if has_diff {
-        let diff_border = if app.focus == Focus::Diff {
-            focused_border
-        } else {
-            unfocused_border
-        };
+    if store.storage() == Storage::Notes {
+        println!("Notes are already keyed by full commit SHA; nothing to migrate.");
+        return Ok(());
+    }
 
-        let diff_title = match app.diff_mode {
-            DiffMode::Stat => " Diff (stat) ",
-            DiffMode::Full => " Diff (full) ",
-            DiffMode::Hidden => "",
-        };
+    let migration = store.migrate(dry_run)?;
 
-        // Build styled lines from diff_lines
-        let lines: Vec<Line> = app
-            .diff_lines
-            .iter()
-            .skip(app.diff_scroll)
-            .map(|dl| Line::from(Span::styled(dl.content.clone(), dl.style)))
-            .collect();
+    if migration.moved.is_empty() && migration.skipped.is_empty() {
+        println!("✓ All records are keyed by full commit SHA");
+        return Ok(());
+    }
 
-        let scroll_info = if !app.diff_lines.is_empty() {
-            format!(" [{}/{}] ", app.diff_scroll + 1, app.diff_lines.len())
-        } else {
-            String::new()
-        };
+    for (from, to) in &migration.moved {
+        println!("  {} -> {}", from, to);
+    }
 
-        let diff = Paragraph::new(lines).block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_style(diff_border)
-                .title(format!("{}{}", diff_title, scroll_info)),
-        );
+    if !migration.skipped.is_empty() {
+        println!();
+        println!("Skipped ({}):", migration.skipped.len());
+        for (path, reason) in &migration.skipped {
+            println!("  {}: {}", path, reason);
+        }
+        println!("Fix the commit field in these records, or move them by hand.");
+    }
 
-        frame.render_widget(diff, main_chunks[1]);
+    println!();
+    if dry_run {
+        println!("Would migrate {} record(s)", migration.moved.len());
+    } else {
+        println!("✓ Migrated {} record(s)", migration.moved.len());
     }
 
-    // Help bar at bottom
-    let help = " q: quit | j/k: scroll | Tab: focus | d: toggle diff | f/b: page ";
-    let help_area = Rect {
-        x: 0,
-        y: frame.area().height - 1,
-        width: frame.area().width,
-        height: 1,
-    };
-    frame.render_widget(
-        Paragraph::new(help).style(Style::default().bg(Color::DarkGray)),
-        help_area,
-    );
+    Ok(())
 }
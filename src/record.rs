@@ -0,0 +1,525 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Version of the record format this build reads and writes. Records
+/// without a `schema_version` predate the field and are version 1.
+pub const RECORD_SCHEMA_VERSION: u32 = 1;
+
+/// A single reasoning record, as described in SPEC.md.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArfRecord {
+    /// Version of the record format (see [`RECORD_SCHEMA_VERSION`]).
+    #[serde(default = "first_schema_version")]
+    pub schema_version: u32,
+    /// Unique ID (see [`crate::id`]). Records written before IDs existed
+    /// have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub what: String,
+    pub why: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub how: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Set by `arf retract`; the record is kept but no longer stands.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retracted: Option<Retraction>,
+    /// Free-form metadata from the `[context]` table, kept as written.
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub context: toml::Table,
+}
+
+impl ArfRecord {
+    /// Create a record with a fresh ID, timestamped now, with the agent taken
+    /// from `ARF_AGENT`.
+    pub fn new(what: impl Into<String>, why: impl Into<String>) -> Self {
+        Self {
+            schema_version: RECORD_SCHEMA_VERSION,
+            id: Some(crate::id::generate()),
+            what: what.into(),
+            why: why.into(),
+            how: None,
+            backup: None,
+            outcome: None,
+            timestamp: Utc::now().to_rfc3339(),
+            commit: None,
+            agent: std::env::var("ARF_AGENT").ok(),
+            retracted: None,
+            context: toml::Table::new(),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+
+    /// Parse a TOML stream: any number of records as `[[record]]` entries.
+    pub fn from_toml_stream(content: &str) -> Result<Vec<Self>, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct Stream {
+            #[serde(default)]
+            record: Vec<ArfRecord>,
+        }
+
+        Ok(toml::from_str::<Stream>(content)?.record)
+    }
+
+    /// Parse a record file, which holds either one record or a TOML stream.
+    pub fn from_toml_file(content: &str) -> Result<Vec<Self>, toml::de::Error> {
+        let table: toml::Table = toml::from_str(content)?;
+        if table.get("record").is_some_and(|r| r.is_array()) {
+            return Self::from_toml_stream(content);
+        }
+        Ok(vec![toml::Value::Table(table).try_into()?])
+    }
+
+    /// Serialize records for a file: a single record as is, several as a
+    /// TOML stream. The inverse of [`ArfRecord::from_toml_file`].
+    pub fn to_toml_file(records: &[Self]) -> Result<String, toml::ser::Error> {
+        match records {
+            [record] => record.to_toml(),
+            _ => Self::to_toml_stream(records),
+        }
+    }
+
+    /// Serialize records as a TOML stream of `[[record]]` entries.
+    pub fn to_toml_stream(records: &[Self]) -> Result<String, toml::ser::Error> {
+        #[derive(Serialize)]
+        struct Stream<'a> {
+            record: &'a [ArfRecord],
+        }
+
+        toml::to_string_pretty(&Stream { record: records })
+    }
+
+    /// Name the record is stored and referenced under: its ID, or
+    /// `<agent>-<YYYYmmdd-HHMMSS>` for records written before IDs existed.
+    pub fn name(&self) -> String {
+        if let Some(ref id) = self.id {
+            return id.clone();
+        }
+        let agent = self.agent.as_deref().unwrap_or("unknown");
+        let time = DateTime::parse_from_rfc3339(&self.timestamp)
+            .map(|t| t.with_timezone(&Utc).format("%Y%m%d-%H%M%S").to_string())
+            .unwrap_or_else(|_| self.timestamp.clone());
+        format!("{}-{}", agent, time)
+    }
+
+    /// Parse a record written outside arf (by hand or by an agent) in any
+    /// [`InputFormat`]. Unlike [`ArfRecord::from_toml`], only `what` and
+    /// `why` are required: a missing `timestamp` or `agent` is filled in as
+    /// [`ArfRecord::new`] would. The record always gets a fresh ID.
+    pub fn from_input(content: &str, format: InputFormat) -> anyhow::Result<Self> {
+        let mut table = match format {
+            InputFormat::Toml => toml::from_str::<toml::Table>(content)
+                .map_err(|e| anyhow!("Invalid TOML: {}", e.message()))?,
+            InputFormat::Json => {
+                let mut value: serde_json::Value =
+                    serde_json::from_str(content).map_err(|e| anyhow!("Invalid JSON: {}", e))?;
+                strip_nulls(&mut value);
+                serde_json::from_value(value)
+                    .map_err(|e| anyhow!("Expected a JSON object: {}", e))?
+            }
+            InputFormat::Markdown => markdown_table(content)?,
+        };
+
+        for field in ["what", "why"] {
+            if !table.contains_key(field) {
+                return Err(anyhow!("Missing required field '{}'", field));
+            }
+        }
+        table.insert("id".into(), crate::id::generate().into());
+        if !table.contains_key("timestamp") {
+            table.insert("timestamp".into(), Utc::now().to_rfc3339().into());
+        }
+        if let (false, Ok(agent)) = (table.contains_key("agent"), std::env::var("ARF_AGENT")) {
+            table.insert("agent".into(), agent.into());
+        }
+
+        let record: ArfRecord = table
+            .try_into()
+            .map_err(|e: toml::de::Error| anyhow!("Invalid record: {}", e.message()))?;
+        record.validate()?;
+        Ok(record)
+    }
+
+    /// Check the fields a record can't do without: non-empty `what` and
+    /// `why`, and an RFC 3339 `timestamp`. See [`crate::validate`] for the
+    /// full schema and lint checks.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.schema_version > RECORD_SCHEMA_VERSION {
+            return Err(anyhow!(
+                "Record uses schema version {}, but this arf only knows up to {}",
+                self.schema_version,
+                RECORD_SCHEMA_VERSION
+            ));
+        }
+        if self.what.trim().is_empty() {
+            return Err(anyhow!("'what' must not be empty"));
+        }
+        if self.why.trim().is_empty() {
+            return Err(anyhow!("'why' must not be empty"));
+        }
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .map_err(|e| anyhow!("'timestamp' is not RFC 3339: {}", e))?;
+        Ok(())
+    }
+
+    /// Parse a `key=value` context entry. The value is read as a TOML value
+    /// (`42`, `0.95`, `true`, `["a", "b"]`), falling back to a plain string.
+    pub fn parse_context(entry: &str) -> anyhow::Result<(String, toml::Value)> {
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected key=value, got '{}'", entry))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(anyhow!("Missing key in '{}'", entry));
+        }
+
+        let value = value.trim();
+        let parsed = toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        Ok((key.to_string(), parsed))
+    }
+
+    /// Context entries formatted as `key = value` for display.
+    pub fn context_lines(&self) -> Vec<String> {
+        self.context
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key, value))
+            .collect()
+    }
+
+    /// Commit SHA shortened for display, or "none".
+    pub fn short_commit(&self) -> &str {
+        self.commit
+            .as_deref()
+            .map(crate::git::short_sha)
+            .unwrap_or("none")
+    }
+}
+
+fn first_schema_version() -> u32 {
+    1
+}
+
+/// Formats accepted by [`ArfRecord::from_input`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// A record as stored, e.g. `what = "..."`.
+    Toml,
+    /// The same fields as a JSON object.
+    Json,
+    /// `### What` / `### Why` / `### How` / `### Backup` sections, as in the
+    /// SPEC.md prompt template, plus an optional `### Context` of TOML lines.
+    Markdown,
+}
+
+impl InputFormat {
+    /// Guess the format from a file extension, else from the content.
+    pub fn detect(extension: Option<&str>, content: &str) -> Self {
+        match extension {
+            Some("toml") => return InputFormat::Toml,
+            Some("json") => return InputFormat::Json,
+            Some("md" | "markdown") => return InputFormat::Markdown,
+            _ => {}
+        }
+
+        let trimmed = content.trim_start();
+        if trimmed.starts_with('{') {
+            InputFormat::Json
+        } else if content
+            .lines()
+            .any(|l| heading(l).is_some_and(|h| h == "what" || h == "why"))
+        {
+            InputFormat::Markdown
+        } else {
+            InputFormat::Toml
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(InputFormat::Toml),
+            "json" => Ok(InputFormat::Json),
+            "markdown" | "md" => Ok(InputFormat::Markdown),
+            _ => Err(anyhow!(
+                "Unknown input format '{}' (expected toml, json, or markdown)",
+                s
+            )),
+        }
+    }
+}
+
+/// The section a markdown heading line starts, if it is one.
+fn heading(line: &str) -> Option<String> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    let name = rest.trim_start_matches('#').trim();
+    (!name.is_empty() && rest.starts_with(['#', ' '])).then(|| name.to_lowercase())
+}
+
+/// Collect `### What`-style sections into record fields. Text outside the
+/// known sections is ignored.
+fn markdown_table(content: &str) -> anyhow::Result<toml::Table> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    let mut in_fence = false;
+    for line in content.lines() {
+        // `#` inside a code block (e.g. a shell comment) is not a heading
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        match heading(line).filter(|_| !in_fence) {
+            Some(name) => sections.push((name, String::new())),
+            None => {
+                if let Some((_, body)) = sections.last_mut() {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+            }
+        }
+    }
+
+    let mut table = toml::Table::new();
+    for (name, body) in sections {
+        let body = body.trim();
+        match name.as_str() {
+            "what" | "why" | "how" | "backup" if !body.is_empty() => {
+                table.insert(name, body.into());
+            }
+            "context" if !body.is_empty() => {
+                let context = toml::from_str::<toml::Table>(body)
+                    .map_err(|e| anyhow!("Invalid ### Context section: {}", e.message()))?;
+                table.insert(name, context.into());
+            }
+            _ => {}
+        }
+    }
+    Ok(table)
+}
+
+/// Drop `null` object members, which TOML can't represent; a field set to
+/// null is treated as absent.
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// Why and when a record was withdrawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Retraction {
+    pub reason: String,
+    pub timestamp: String,
+}
+
+impl Retraction {
+    /// A retraction timestamped now.
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+impl fmt::Display for Retraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.reason, self.timestamp)
+    }
+}
+
+/// Result of the action a record describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutcomeStatus {
+    Success,
+    Failure,
+    Partial,
+}
+
+impl FromStr for OutcomeStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(OutcomeStatus::Success),
+            "failure" => Ok(OutcomeStatus::Failure),
+            "partial" => Ok(OutcomeStatus::Partial),
+            _ => Err(anyhow!(
+                "Unknown outcome '{}' (expected success, failure, or partial)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutcomeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutcomeStatus::Success => "success",
+            OutcomeStatus::Failure => "failure",
+            OutcomeStatus::Partial => "partial",
+        })
+    }
+}
+
+/// A record's outcome, reported after the action ran.
+///
+/// SPEC.md allows either a bare status or one with details, so both TOML
+/// forms are accepted:
+///
+/// ```toml
+/// outcome = "success"
+///
+/// [outcome]
+/// status = "failure"
+/// reason = "tests failed"
+/// ```
+///
+/// It is written back as a bare string when there is no reason.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "OutcomeRepr", into = "OutcomeRepr")]
+pub struct Outcome {
+    pub status: OutcomeStatus,
+    pub reason: Option<String>,
+}
+
+impl Outcome {
+    pub fn new(status: OutcomeStatus, reason: Option<String>) -> Self {
+        Self { status, reason }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            Some(ref reason) => write!(f, "{} ({})", self.status, reason),
+            None => write!(f, "{}", self.status),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum OutcomeRepr {
+    Status(OutcomeStatus),
+    Detailed {
+        #[serde(alias = "outcome")]
+        status: OutcomeStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+}
+
+impl From<OutcomeRepr> for Outcome {
+    fn from(repr: OutcomeRepr) -> Self {
+        match repr {
+            OutcomeRepr::Status(status) => Outcome::new(status, None),
+            OutcomeRepr::Detailed { status, reason } => Outcome::new(status, reason),
+        }
+    }
+}
+
+impl From<Outcome> for OutcomeRepr {
+    fn from(outcome: Outcome) -> Self {
+        match outcome.reason {
+            None => OutcomeRepr::Status(outcome.status),
+            reason => OutcomeRepr::Detailed {
+                status: outcome.status,
+                reason,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_by_extension_first() {
+        assert_eq!(
+            InputFormat::detect(Some("json"), "what = 1"),
+            InputFormat::Json
+        );
+        assert_eq!(InputFormat::detect(Some("md"), "{}"), InputFormat::Markdown);
+        assert_eq!(
+            InputFormat::detect(Some("toml"), "### What"),
+            InputFormat::Toml
+        );
+    }
+
+    #[test]
+    fn detect_by_content() {
+        assert_eq!(
+            InputFormat::detect(None, "  {\"what\": 1}"),
+            InputFormat::Json
+        );
+        let markdown = "Notes\n\n## Why\nbecause\n";
+        assert_eq!(
+            InputFormat::detect(Some("txt"), markdown),
+            InputFormat::Markdown
+        );
+        let toml = "what = \"x\"\n# What\n";
+        assert_eq!(InputFormat::detect(None, toml), InputFormat::Markdown);
+        assert_eq!(InputFormat::detect(None, "what = \"x\""), InputFormat::Toml);
+        // `#What` without a space is a hashtag, not a heading
+        assert_eq!(InputFormat::detect(None, "#What\n"), InputFormat::Toml);
+    }
+
+    #[test]
+    fn markdown_sections() {
+        let content = "Preamble is ignored\n\n### What\nAdd retries\n\n### Why\nFlaky\nnetwork\n\n\
+                       ### Notes\nignored\n\n### How\n```sh\n# not a heading\nretry 3\n```\n";
+        let table = markdown_table(content).unwrap();
+        assert_eq!(table["what"].as_str(), Some("Add retries"));
+        assert_eq!(table["why"].as_str(), Some("Flaky\nnetwork"));
+        assert_eq!(
+            table["how"].as_str(),
+            Some("```sh\n# not a heading\nretry 3\n```")
+        );
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn markdown_empty_sections_are_absent() {
+        let table = markdown_table("### What\n\n### Backup\n   \n").unwrap();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn markdown_context_is_toml() {
+        let table = markdown_table("### Context\nticket = \"ARF-1\"\nretries = 3\n").unwrap();
+        let context = table["context"].as_table().unwrap();
+        assert_eq!(context["ticket"].as_str(), Some("ARF-1"));
+        assert_eq!(context["retries"].as_integer(), Some(3));
+
+        let err = markdown_table("### Context\nnot toml\n").unwrap_err();
+        assert!(err.to_string().contains("Invalid ### Context section"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Machine-readable output for `--format json|toml|ndjson`.
+//!
+//! Every document has the same envelope:
+//!
+//! ```json
+//! { "schema_version": 1, "kind": "log", "items": [ ... ] }
+//! ```
+//!
+//! NDJSON writes one item per line instead, with `schema_version` and `kind`
+//! alongside the item's own fields. Fields are only ever added within a
+//! schema version; renaming or removing one bumps [`SCHEMA_VERSION`].
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::git::{CommitInfo, FileStat};
+use crate::record::ArfRecord;
+use crate::store::StoredRecord;
+
+/// Version of the output schema described in this module.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How a command prints its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Human-readable text.
+    #[default]
+    Text,
+    Json,
+    Toml,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "ndjson" => Ok(Format::Ndjson),
+            _ => Err(anyhow!(
+                "Unknown format '{}' (expected text, json, toml, or ndjson)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Text => "text",
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Ndjson => "ndjson",
+        })
+    }
+}
+
+/// A stored record and where it lives.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordEntry {
+    /// The record's ID (see [`ArfRecord::name`]).
+    pub id: String,
+    /// Path on the `arf` branch, or the commit SHA in notes mode.
+    pub path: String,
+    pub record: ArfRecord,
+}
+
+impl From<StoredRecord> for RecordEntry {
+    fn from(stored: StoredRecord) -> Self {
+        Self {
+            id: stored.record.name(),
+            path: stored.path.to_string_lossy().to_string(),
+            record: stored.record,
+        }
+    }
+}
+
+/// An item of `arf log`: a record with the commit it explains. `commit` is
+/// absent when that commit doesn't exist in this repository.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogItem {
+    pub commit: Option<CommitInfo>,
+    #[serde(flatten)]
+    pub entry: RecordEntry,
+}
+
+/// An item of `arf graph`: a commit and its records.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphItem {
+    pub commit: CommitInfo,
+    pub records: Vec<RecordEntry>,
+    /// Records found in the commit message, when none are stored.
+    pub embedded: Vec<ArfRecord>,
+}
+
+/// The item of `arf diff`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffItem {
+    pub commit: CommitInfo,
+    pub records: Vec<RecordEntry>,
+    pub files: Vec<FileStat>,
+    /// The full patch, with `--full`.
+    pub patch: Option<String>,
+}
+
+/// An item of `arf coverage`: a commit in the range and whether it has
+/// reasoning.
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageItem {
+    pub commit: CommitInfo,
+    pub covered: bool,
+    /// Valid records attached, not counting retracted ones.
+    pub records: usize,
+}
+
+/// An item of `arf config list`: a setting and the layer it comes from.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigItem {
+    pub key: String,
+    pub values: Vec<String>,
+    /// `.arfconfig`, `git config`, or `environment`; `None` if unset.
+    pub source: Option<String>,
+}
+
+/// An item of `arf spec list` or `arf spec show`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecItem {
+    pub name: String,
+    /// The spec itself; only `arf spec show` includes it.
+    pub content: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Document<'a, T> {
+    schema_version: u32,
+    kind: &'a str,
+    items: &'a [T],
+}
+
+#[derive(Serialize)]
+struct Line<'a, T> {
+    schema_version: u32,
+    kind: &'a str,
+    #[serde(flatten)]
+    item: &'a T,
+}
+
+/// Render `items` of the given `kind` (`log`, `graph`, ...) in `format`.
+/// Text output is up to each command, so [`Format::Text`] is an error here.
+pub fn render<T: Serialize>(format: Format, kind: &str, items: &[T]) -> Result<String> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        kind,
+        items,
+    };
+
+    Ok(match format {
+        Format::Text => return Err(anyhow!("Text output is not rendered by the schema")),
+        Format::Json => serde_json::to_string_pretty(&document)? + "\n",
+        Format::Toml => toml::to_string_pretty(&document)?,
+        Format::Ndjson => items
+            .iter()
+            .map(|item| {
+                serde_json::to_string(&Line {
+                    schema_version: SCHEMA_VERSION,
+                    kind,
+                    item,
+                })
+                .map(|line| line + "\n")
+            })
+            .collect::<Result<String, _>>()?,
+    })
+}
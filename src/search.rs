@@ -0,0 +1,568 @@
+//! Full-text search over records (`arf search`).
+//!
+//! The index is an inverted index from terms to the records containing them,
+//! kept under the git directory (`arf/search-index.json`). It remembers the
+//! commit of the `arf` branch (or notes ref) it was built from, so updating it
+//! only re-reads the files that changed since. Results are ranked with BM25.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::git;
+use crate::record::ArfRecord;
+use crate::store::{file_records, RecordStore, Storage};
+
+/// Bumped whenever the on-disk layout or tokenizer changes, forcing a rebuild.
+const INDEX_VERSION: u32 = 1;
+
+/// BM25 term-frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document-length normalization.
+const B: f64 = 0.75;
+
+/// One indexed record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Doc {
+    /// File (or, with notes, commit) the record was read from.
+    pub source: String,
+    /// The record's ID.
+    pub id: String,
+    pub commit: Option<String>,
+    pub what: String,
+    /// Searchable text: what, why, how, backup, and context values.
+    pub text: String,
+    /// Number of terms in `text`.
+    len: u32,
+}
+
+/// A search result.
+#[derive(Debug, Clone)]
+pub struct Hit<'a> {
+    pub doc: &'a Doc,
+    pub score: f64,
+}
+
+/// The search index.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    storage: String,
+    /// Commit of the `arf` branch or notes ref last indexed.
+    tip: Option<String>,
+    /// Documents by number; removed documents leave a hole.
+    docs: Vec<Option<Doc>>,
+    /// Term to (document number, term frequency).
+    postings: BTreeMap<String, BTreeMap<u32, u32>>,
+    /// Document numbers for each source.
+    sources: BTreeMap<String, Vec<u32>>,
+    /// Total number of terms across live documents.
+    total_len: u64,
+}
+
+impl Default for Index {
+    /// An empty index; the first [`Index::update`] fills it.
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            storage: String::new(),
+            tip: None,
+            docs: Vec::new(),
+            postings: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            total_len: 0,
+        }
+    }
+}
+
+impl Index {
+    /// Where the index for `repo` is kept: in the shared git directory, so
+    /// every worktree uses the same one.
+    pub fn path(repo: &Path) -> Result<PathBuf> {
+        Ok(git::common_dir(repo)?.join("arf").join("search-index.json"))
+    }
+
+    /// Load the index from disk, or start an empty one if it is missing,
+    /// unreadable, or from another version.
+    pub fn load(repo: &Path) -> Result<Self> {
+        let path = Self::path(repo)?;
+        let index = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Index>(&content).ok())
+            .filter(|index| index.version == INDEX_VERSION);
+        Ok(index.unwrap_or_default())
+    }
+
+    pub fn save(&self, repo: &Path) -> Result<()> {
+        let path = Self::path(repo)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write then rename, so a concurrent reader never sees half an index
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Commit of the `arf` branch or notes ref the index reflects.
+    pub fn tip(&self) -> Option<&str> {
+        self.tip.as_deref()
+    }
+
+    /// Number of records in the index.
+    pub fn len(&self) -> usize {
+        self.docs.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bring the index up to date with the store, re-reading only the files
+    /// changed since it was last updated. Returns the number of sources
+    /// re-indexed.
+    pub fn update(&mut self, store: &RecordStore) -> Result<usize> {
+        let storage = store.storage().to_string();
+        let tip = match store.storage() {
+            Storage::Notes => store.notes().tip(),
+            Storage::Branch => store.backend().tip(),
+        };
+
+        if storage != self.storage {
+            *self = Index {
+                storage,
+                ..Default::default()
+            };
+        }
+        if tip == self.tip {
+            return Ok(0);
+        }
+
+        let changed = match (&self.tip, &tip) {
+            (Some(old), Some(new)) => changed_sources(store, old, new).ok(),
+            _ => None,
+        };
+        let count = match (changed, store.storage()) {
+            (Some(sources), Storage::Branch) => {
+                for source in &sources {
+                    self.remove_source(source);
+                    for record in read_file(store, source)? {
+                        self.add(&record, source.clone());
+                    }
+                }
+                sources.len()
+            }
+            (Some(sources), Storage::Notes) => self.update_notes(store, sources)?,
+            (None, _) => self.rebuild(store)?,
+        };
+        self.compact();
+
+        self.tip = tip;
+        Ok(count)
+    }
+
+    /// Re-index the notes on `changed` commits. Records come from
+    /// [`RecordStore::list`], as in a rebuild: it drops the copies a rewrite
+    /// leaves behind, so a change to one note can hide or reveal records in
+    /// another, and those notes are re-indexed too.
+    fn update_notes(&mut self, store: &RecordStore, changed: Vec<String>) -> Result<usize> {
+        let mut listed: BTreeMap<String, Vec<ArfRecord>> = BTreeMap::new();
+        for stored in store.list()? {
+            let source = stored.path.to_string_lossy().to_string();
+            listed.entry(source).or_default().push(stored.record);
+        }
+
+        let mut sources: HashSet<String> = changed.into_iter().collect();
+        for source in listed.keys().chain(self.sources.keys()) {
+            let indexed: Vec<&str> = self
+                .sources
+                .get(source)
+                .into_iter()
+                .flatten()
+                .filter_map(|&n| Some(self.docs.get(n as usize)?.as_ref()?.id.as_str()))
+                .collect();
+            let current: Vec<String> = listed
+                .get(source)
+                .into_iter()
+                .flatten()
+                .map(|r| r.name())
+                .collect();
+            if indexed != current {
+                sources.insert(source.clone());
+            }
+        }
+
+        for source in &sources {
+            self.remove_source(source);
+            for record in listed.get(source).into_iter().flatten() {
+                self.add(record, source.clone());
+            }
+        }
+        Ok(sources.len())
+    }
+
+    /// Drop every document and index the store from scratch.
+    fn rebuild(&mut self, store: &RecordStore) -> Result<usize> {
+        *self = Index {
+            storage: std::mem::take(&mut self.storage),
+            ..Default::default()
+        };
+
+        let mut sources = HashSet::new();
+        for stored in store.list()? {
+            let source = stored.path.to_string_lossy().to_string();
+            sources.insert(source.clone());
+            self.add(&stored.record, source);
+        }
+        Ok(sources.len())
+    }
+
+    /// Records matching `query`, best first. Any term may match; records
+    /// matching more (and rarer) terms rank higher.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Hit<'_>> {
+        let n = self.len() as f64;
+        if n == 0.0 {
+            return Vec::new();
+        }
+        let avg_len = self.total_len as f64 / n;
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scores: HashMap<u32, f64> = HashMap::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f64;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+
+            for (&doc, &tf) in postings {
+                let Some(Some(d)) = self.docs.get(doc as usize) else {
+                    continue;
+                };
+                let tf = tf as f64;
+                let norm = K1 * (1.0 - B + B * d.len as f64 / avg_len);
+                *scores.entry(doc).or_default() += idf * tf * (K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut hits: Vec<Hit> = scores
+            .into_iter()
+            .filter_map(|(doc, score)| {
+                let doc = self.docs.get(doc as usize)?.as_ref()?;
+                Some(Hit { doc, score })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.doc.id.cmp(&b.doc.id))
+        });
+        hits.truncate(limit);
+        hits
+    }
+
+    fn add(&mut self, record: &ArfRecord, source: String) {
+        let text = searchable_text(record);
+        let terms = tokenize(&text);
+        let number = self.docs.len() as u32;
+
+        let mut frequencies: HashMap<&str, u32> = HashMap::new();
+        for term in &terms {
+            *frequencies.entry(term).or_default() += 1;
+        }
+        for (term, tf) in frequencies {
+            self.postings
+                .entry(term.to_string())
+                .or_default()
+                .insert(number, tf);
+        }
+
+        self.total_len += terms.len() as u64;
+        self.sources.entry(source.clone()).or_default().push(number);
+        self.docs.push(Some(Doc {
+            source,
+            id: record.name(),
+            commit: record.commit.clone(),
+            what: record.what.clone(),
+            text,
+            len: terms.len() as u32,
+        }));
+    }
+
+    /// Renumber the documents once removed ones are most of `docs`, so the
+    /// index doesn't grow with every update.
+    fn compact(&mut self) {
+        if self.docs.len() <= 2 * self.len() {
+            return;
+        }
+
+        let mut numbers: HashMap<u32, u32> = HashMap::new();
+        for (old, doc) in std::mem::take(&mut self.docs).into_iter().enumerate() {
+            if doc.is_some() {
+                numbers.insert(old as u32, self.docs.len() as u32);
+                self.docs.push(doc);
+            }
+        }
+        for postings in self.postings.values_mut() {
+            *postings = postings
+                .iter()
+                .filter_map(|(doc, &tf)| Some((*numbers.get(doc)?, tf)))
+                .collect();
+        }
+        for docs in self.sources.values_mut() {
+            docs.retain_mut(|doc| match numbers.get(doc) {
+                Some(&new) => {
+                    *doc = new;
+                    true
+                }
+                None => false,
+            });
+        }
+    }
+
+    fn remove_source(&mut self, source: &str) {
+        for number in self.sources.remove(source).unwrap_or_default() {
+            let Some(doc) = self.docs.get_mut(number as usize).and_then(|d| d.take()) else {
+                continue;
+            };
+            self.total_len -= doc.len as u64;
+            for term in tokenize(&doc.text) {
+                if let Some(postings) = self.postings.get_mut(&term) {
+                    postings.remove(&number);
+                    if postings.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Up to `width` characters of `text` around the first term of `query`
+/// found in it, as segments flagged `true` where a query term matches.
+/// Line breaks are flattened so the snippet fits on one line.
+pub fn snippet(text: &str, query: &str, width: usize) -> Vec<(String, bool)> {
+    let terms: HashSet<String> = tokenize(query).into_iter().collect();
+    let chars: Vec<char> = text
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    // Words as (start, end) char offsets, and whether each matches
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_word(chars[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && is_word(chars[i]) {
+            i += 1;
+        }
+        let word: String = chars[start..i].iter().collect::<String>().to_lowercase();
+        words.push((start, i, terms.contains(&word)));
+    }
+
+    let first = words.iter().find(|w| w.2).map_or(0, |w| w.0);
+    let start = first.saturating_sub(width / 3);
+    let end = (start + width).min(chars.len());
+
+    let mut segments: Vec<(String, bool)> = Vec::new();
+    let mut push = |text: String, hit: bool| {
+        if text.is_empty() {
+            return;
+        }
+        match segments.last_mut() {
+            Some((last, last_hit)) if *last_hit == hit => last.push_str(&text),
+            _ => segments.push((text, hit)),
+        }
+    };
+
+    if start > 0 {
+        push("…".to_string(), false);
+    }
+    let mut pos = start;
+    for &(word_start, word_end, hit) in &words {
+        if word_end <= start || !hit {
+            continue;
+        }
+        if word_start >= end {
+            break;
+        }
+        let word_start = word_start.max(start);
+        let word_end = word_end.min(end);
+        push(chars[pos..word_start].iter().collect(), false);
+        push(chars[word_start..word_end].iter().collect(), true);
+        pos = word_end;
+    }
+    push(chars[pos..end].iter().collect(), false);
+    if end < chars.len() {
+        push("…".to_string(), false);
+    }
+    segments
+}
+
+/// Lowercased runs of letters, digits, and underscores.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+fn searchable_text(record: &ArfRecord) -> String {
+    let mut parts = vec![record.what.clone(), record.why.clone()];
+    parts.extend(record.how.clone());
+    parts.extend(record.backup.clone());
+    parts.extend(record.context_lines());
+    parts.join("\n")
+}
+
+/// Sources (record files, or annotated commits with notes) that differ
+/// between two commits of the branch or notes ref.
+fn changed_sources(store: &RecordStore, old: &str, new: &str) -> Result<Vec<String>> {
+    let mut args = vec!["diff-tree", "-r", "--no-renames", "--name-only", old, new];
+    if store.storage() == Storage::Branch {
+        args.extend(["--", "records"]);
+    }
+    let out = git::run(store.repo(), &args)?;
+
+    let mut sources: Vec<String> = out
+        .lines()
+        .map(|path| match store.storage() {
+            // Notes may be fanned out as ab/cdef...
+            Storage::Notes => path.replace('/', ""),
+            Storage::Branch => path.to_string(),
+        })
+        .collect();
+    sources.dedup();
+    Ok(sources)
+}
+
+/// The records currently in the record file `source`.
+fn read_file(store: &RecordStore, source: &str) -> Result<Vec<ArfRecord>> {
+    if !source.ends_with(".toml") {
+        return Ok(Vec::new());
+    }
+    Ok(match store.backend().read(source)? {
+        Some(content) => file_records(source, &content)
+            .into_iter()
+            .map(|stored| stored.record)
+            .collect(),
+        None => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(records: &[(&str, &str, &str)]) -> Index {
+        let mut index = Index::default();
+        for &(source, what, why) in records {
+            index.add(&ArfRecord::new(what, why), source.to_string());
+        }
+        index
+    }
+
+    fn found(index: &Index, query: &str) -> Vec<String> {
+        index
+            .search(query, 10)
+            .into_iter()
+            .map(|hit| hit.doc.what.clone())
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_lowercases_words() {
+        assert_eq!(
+            tokenize("Fix the HTTP_client, v2!"),
+            ["fix", "the", "http_client", "v2"]
+        );
+        assert_eq!(tokenize("Größe – naïve"), ["größe", "naïve"]);
+        assert!(tokenize("  --  ").is_empty());
+    }
+
+    #[test]
+    fn snippet_marks_matches() {
+        let segments = snippet("alpha Beta gamma", "beta", 100);
+        assert_eq!(
+            segments,
+            [
+                ("alpha ".to_string(), false),
+                ("Beta".to_string(), true),
+                (" gamma".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn snippet_centres_on_the_first_match() {
+        let text = format!("{} needle {}", "hay ".repeat(50), "hay ".repeat(50));
+        let segments = snippet(&text, "needle", 30);
+        assert_eq!(segments.first().unwrap().0.chars().next(), Some('…'));
+        assert!(segments.contains(&("needle".to_string(), true)));
+        assert!(segments.last().unwrap().0.ends_with('…'));
+        let width: usize = segments.iter().map(|(s, _)| s.chars().count()).sum();
+        assert!(width <= 32, "{}", width);
+    }
+
+    #[test]
+    fn search_ranks_more_and_rarer_matches_first() {
+        let index = index(&[
+            ("a", "Retry failed uploads", "Uploads drop on flaky links"),
+            ("b", "Compress uploads", "Bandwidth is limited"),
+            ("c", "Rename the config file", "Name was confusing"),
+        ]);
+
+        assert_eq!(found(&index, "retry"), ["Retry failed uploads"]);
+        assert_eq!(
+            found(&index, "uploads retry"),
+            ["Retry failed uploads", "Compress uploads"]
+        );
+        // "config" is in one record, "uploads" in two: the rarer term wins
+        let hits = index.search("config uploads", 10);
+        assert_eq!(hits[0].doc.what, "Rename the config file");
+        assert!(found(&index, "nothing").is_empty());
+    }
+
+    #[test]
+    fn search_is_empty_on_an_empty_index() {
+        assert!(Index::default().search("anything", 10).is_empty());
+    }
+
+    #[test]
+    fn compact_drops_removed_documents() {
+        let mut index = index(&[
+            ("a", "Add the parser", "Input was unread"),
+            ("b", "Fix the parser", "It dropped lines"),
+            ("c", "Document the parser", "Nobody knew the syntax"),
+        ]);
+        index.remove_source("a");
+        index.remove_source("b");
+        index.compact();
+
+        assert_eq!(index.docs.len(), 1);
+        assert_eq!(index.sources["c"], [0]);
+        assert_eq!(found(&index, "parser"), ["Document the parser"]);
+
+        index.add(&ArfRecord::new("Test the parser", "It broke"), "d".into());
+        assert_eq!(found(&index, "parser").len(), 2);
+        assert_eq!(
+            index.total_len,
+            index
+                .docs
+                .iter()
+                .flatten()
+                .map(|d| d.len as u64)
+                .sum::<u64>()
+        );
+    }
+}
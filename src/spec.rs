@@ -0,0 +1,248 @@
+//! Specs: task definitions kept on the ARF branch as `specs/<name>.arf`.
+//!
+//! A spec is TOML in the same terms as a record: `what` the task is and
+//! `why` it matters, plus what must hold when it's done and the steps to
+//! get there.
+//!
+//! ```toml
+//! schema_version = 1
+//! what = "Add rate limiting to the public API"
+//! why = "Scrapers exhausted the database pool twice this month"
+//! status = "active"            # draft, active, done, or dropped
+//! acceptance = ["Clients over 100 requests a minute get a 429"]
+//!
+//! [[task]]
+//! what = "Add a token bucket per API key"
+//! done = true
+//! ```
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::validate::{self, Problem, Report, Severity};
+
+/// Version of the spec schema this crate reads and writes.
+pub const SPEC_SCHEMA_VERSION: u32 = 1;
+
+pub const STATUSES: &[&str] = &["draft", "active", "done", "dropped"];
+
+/// Top-level fields of schema version 1.
+const FIELDS: &[&str] = &[
+    "schema_version",
+    "what",
+    "why",
+    "how",
+    "backup",
+    "status",
+    "created",
+    "acceptance",
+    "task",
+    "context",
+];
+
+const TASK_FIELDS: &[&str] = &["what", "how", "done"];
+
+fn first_schema_version() -> u32 {
+    1
+}
+
+fn draft() -> String {
+    "draft".to_string()
+}
+
+/// A spec as stored in `specs/<name>.arf`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Spec {
+    #[serde(default = "first_schema_version")]
+    pub schema_version: u32,
+    pub what: String,
+    pub why: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub how: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    #[serde(default = "draft")]
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// What must be true when the spec is done.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acceptance: Vec<String>,
+    #[serde(default, rename = "task", skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<Task>,
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub context: toml::Table,
+}
+
+/// A step of a spec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    pub what: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub how: Option<String>,
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl Spec {
+    /// Parse a spec, failing on anything [`check`] reports as an error.
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let errors: Vec<String> = check(content)
+            .into_iter()
+            .filter(|p| p.severity == Severity::Error)
+            .map(|p| p.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(anyhow!("Invalid spec: {}", errors.join("; ")));
+        }
+        toml::from_str(content).map_err(|e| anyhow!("Invalid spec: {}", e.message()))
+    }
+
+    /// Tasks marked done, out of all tasks.
+    pub fn progress(&self) -> (usize, usize) {
+        let done = self.tasks.iter().filter(|t| t.done).count();
+        (done, self.tasks.len())
+    }
+}
+
+/// Whether `name` can name a spec file: letters, digits, `-`, `_`, and `.`,
+/// not starting with `.` or `-`.
+pub fn check_name(name: &str) -> Result<()> {
+    let ok = !name.is_empty()
+        && !name.starts_with(['.', '-'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if ok {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "'{}' is not a valid spec name (use letters, digits, '-', '_', and '.')",
+            name
+        ))
+    }
+}
+
+/// A new spec to fill in. Fields not given are left empty, so the spec
+/// doesn't validate until they are.
+pub fn template(what: Option<&str>, why: Option<&str>) -> String {
+    let quote = |s: Option<&str>| toml::Value::String(s.unwrap_or_default().to_string());
+    format!(
+        r#"schema_version = {version}
+
+# The task, as a concrete change ("Add rate limiting to the public API")
+what = {what}
+# The problem it solves, and why this approach
+why = {why}
+# how = "Approach, if already decided"
+# backup = "How to undo it if it goes wrong"
+
+status = "draft"   # draft, active, done, or dropped
+created = "{created}"
+
+# What must be true when it's done
+acceptance = []
+
+# Steps; set done = true as each one lands
+# [[task]]
+# what = "Add a token bucket per API key"
+# how = "Middleware in src/api/mod.rs"
+"#,
+        version = SPEC_SCHEMA_VERSION,
+        what = quote(what),
+        why = quote(why),
+        created = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+    )
+}
+
+/// Check a spec file's `content` against the schema.
+pub fn check(content: &str) -> Vec<Problem> {
+    let table: toml::Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(e) => return vec![Problem::invalid_toml(&e)],
+    };
+    let mut problems = Vec::new();
+
+    validate::check_schema_version(&table, SPEC_SCHEMA_VERSION, &mut problems);
+    validate::check_required_text(&table, &["what", "why"], &mut problems);
+    validate::check_optional_text(&table, &["how", "backup"], &mut problems);
+
+    match table.get("status") {
+        None => {}
+        Some(toml::Value::String(s)) if STATUSES.contains(&s.as_str()) => {}
+        Some(_) => problems.push(Problem::error(
+            "status",
+            format!("must be one of {}", STATUSES.join(", ")),
+        )),
+    }
+
+    match table.get("created") {
+        None => {}
+        Some(toml::Value::String(s)) => {
+            if let Err(e) = DateTime::parse_from_rfc3339(s) {
+                problems.push(Problem::error(
+                    "created",
+                    format!("'{}' is not RFC 3339: {}", s, e),
+                ));
+            }
+        }
+        Some(_) => problems.push(Problem::error("created", "must be an RFC 3339 string")),
+    }
+
+    match table.get("acceptance") {
+        None => {}
+        Some(toml::Value::Array(items)) if items.iter().all(|i| i.is_str()) => {}
+        Some(_) => problems.push(Problem::error("acceptance", "must be a list of strings")),
+    }
+
+    match table.get("task") {
+        None => {}
+        Some(toml::Value::Array(tasks)) => {
+            for (i, task) in tasks.iter().enumerate() {
+                let field = format!("task {}", i + 1);
+                let Some(task) = task.as_table() else {
+                    problems.push(Problem::error(&field, "must be a table"));
+                    continue;
+                };
+                match task.get("what") {
+                    Some(toml::Value::String(s)) if !s.trim().is_empty() => {}
+                    _ => problems.push(Problem::error(&field, "needs a non-empty 'what'")),
+                }
+                if task.get("how").is_some_and(|v| !v.is_str()) {
+                    problems.push(Problem::error(&field, "'how' must be a string"));
+                }
+                if task.get("done").is_some_and(|v| !v.is_bool()) {
+                    problems.push(Problem::error(&field, "'done' must be true or false"));
+                }
+                let prefix = format!("{}.", field);
+                validate::check_unknown(task, TASK_FIELDS, &prefix, &mut problems);
+            }
+        }
+        Some(_) => problems.push(Problem::error(
+            "task",
+            "must be an array of [[task]] tables",
+        )),
+    }
+
+    if table.get("context").is_some_and(|c| !c.is_table()) {
+        problems.push(Problem::error("context", "must be a table"));
+    }
+
+    validate::check_unknown(&table, FIELDS, "", &mut problems);
+
+    problems
+}
+
+/// [`check`] a stored spec, as a report for `arf spec validate`.
+pub fn report(name: &str, content: &str) -> Report {
+    let what = toml::from_str::<toml::Table>(content)
+        .ok()
+        .and_then(|t| t.get("what")?.as_str().map(String::from));
+    Report {
+        source: format!("specs/{}.arf", name),
+        entry: None,
+        what,
+        problems: check(content),
+    }
+}
@@ -0,0 +1,1043 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backend::{Backend, Change, Notes, Objects, Worktree};
+use crate::config::Config;
+use crate::record::{ArfRecord, OutcomeStatus};
+use crate::NOTES_REF;
+use crate::{git, id};
+
+const README: &str = r#"# ARF Records
+
+This branch contains Agent Reasoning Format records.
+
+Records are organized by full commit SHA:
+```
+records/
+  <commit-sha>/
+    <record-id>.toml
+```
+
+See https://github.com/ducks/arf for the ARF specification.
+"#;
+
+/// Where records are kept, chosen at `arf init` and read from the `storage`
+/// config key like any other setting (see [`crate::config`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Storage {
+    /// Files under `records/` on the `arf` branch.
+    #[default]
+    Branch,
+    /// Git notes under `refs/notes/arf`.
+    Notes,
+}
+
+impl FromStr for Storage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "branch" => Ok(Storage::Branch),
+            "notes" => Ok(Storage::Notes),
+            _ => Err(anyhow!(
+                "Unknown storage '{}' (expected branch or notes)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Storage::Branch => "branch",
+            Storage::Notes => "notes",
+        })
+    }
+}
+
+/// A record together with the file it was loaded from.
+#[derive(Debug, Clone)]
+pub struct StoredRecord {
+    /// Path of the record file, relative to the root of the `arf` branch.
+    /// With notes storage, this is the SHA of the annotated commit.
+    pub path: PathBuf,
+    pub record: ArfRecord,
+}
+
+/// Filters for [`RecordStore::query`]. Every filter that is set must match.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Only records attached to this commit (any revision git understands).
+    pub commit: Option<String>,
+    /// Only records written by this agent.
+    pub agent: Option<String>,
+    /// Only records timestamped at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only records timestamped at or before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only records with this outcome.
+    pub outcome: Option<OutcomeStatus>,
+    /// Only records whose what, why, how, or backup contains this text
+    /// (case-insensitive).
+    pub grep: Option<String>,
+    /// Only records whose commit touched one of these paths.
+    pub paths: Vec<String>,
+    /// Only records whose commit is in this revision range: a branch,
+    /// `main..feature`, or several revisions separated by spaces.
+    pub range: Option<String>,
+    /// Maximum number of records to return.
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    /// Whether any filter besides `commit` and `limit` is set.
+    pub fn has_filters(&self) -> bool {
+        self.agent.is_some()
+            || self.since.is_some()
+            || self.until.is_some()
+            || self.outcome.is_some()
+            || self.grep.is_some()
+            || !self.paths.is_empty()
+            || self.range.is_some()
+    }
+
+    /// Whether `record` passes the filters that don't need git.
+    pub fn matches(&self, record: &ArfRecord) -> bool {
+        if let Some(ref agent) = self.agent {
+            if record.agent.as_deref() != Some(agent.as_str()) {
+                return false;
+            }
+        }
+        if let Some(status) = self.outcome {
+            if record.outcome.as_ref().map(|o| o.status) != Some(status) {
+                return false;
+            }
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Ok(time) = DateTime::parse_from_rfc3339(&record.timestamp) else {
+                return false;
+            };
+            let time = time.with_timezone(&Utc);
+            if self.since.is_some_and(|since| time < since)
+                || self.until.is_some_and(|until| time > until)
+            {
+                return false;
+            }
+        }
+        if let Some(ref grep) = self.grep {
+            let needle = grep.to_lowercase();
+            let found = [
+                Some(&record.what),
+                Some(&record.why),
+                record.how.as_ref(),
+                record.backup.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&needle));
+            if !found {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Parse a time for [`Query::since`] and [`Query::until`]: RFC 3339,
+/// `YYYY-MM-DD` (midnight UTC), `today`, `yesterday`, or an age such as
+/// `30m`, `12h`, `7d`, or `2w`.
+pub fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    let now = Utc::now();
+    let midnight = |date: NaiveDate| date.and_time(NaiveTime::MIN).and_utc();
+
+    match s {
+        "now" => return Ok(now),
+        "today" => return Ok(midnight(now.date_naive())),
+        "yesterday" => return Ok(midnight(now.date_naive()) - Duration::days(1)),
+        _ => {}
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(midnight(date));
+    }
+
+    // The unit is the last character, which needn't be ASCII
+    let split = s.char_indices().last().map_or(0, |(i, _)| i);
+    let (amount, unit) = s.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| anyhow!("Can't parse time '{}' (try 2026-01-31, 7d, or 12h)", s))?;
+    let age = match unit {
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => return Err(anyhow!("Unknown time unit in '{}' (use m, h, d, or w)", s)),
+    };
+    age.and_then(|age| now.checked_sub_signed(age))
+        .ok_or_else(|| anyhow!("Time '{}' is out of range", s))
+}
+
+/// Records stored on the `arf` branch, or as notes when so configured.
+///
+/// If the branch is checked out in some worktree (usually `.arf/` in the main
+/// checkout) the store works through it; otherwise it reads and writes git
+/// objects directly. Specs always live on the branch.
+#[derive(Debug)]
+pub struct RecordStore {
+    repo: PathBuf,
+    backend: Box<dyn Backend>,
+    worktree: Option<PathBuf>,
+    config: Config,
+    notes: Notes,
+}
+
+impl RecordStore {
+    /// Open the store for the repository at `repo`.
+    ///
+    /// This succeeds even if the `arf` branch doesn't exist yet: reads return
+    /// nothing and the first write creates it.
+    pub fn open(repo: impl Into<PathBuf>) -> Result<Self> {
+        let repo = repo.into();
+
+        if !git::is_repo(&repo) {
+            return Err(anyhow!("Not a git repository. Run 'git init' first."));
+        }
+
+        let config = Config::load(&repo)?;
+        let worktree = git::worktree_of(&repo, &config.branch_ref());
+        let backend: Box<dyn Backend> = match worktree {
+            Some(ref dir) => Box::new(Worktree::new(dir)),
+            None => Box::new(Objects::new(&repo, &config.branch, &config.remote)),
+        };
+
+        Ok(Self {
+            notes: Notes::new(&repo, NOTES_REF),
+            repo,
+            backend,
+            worktree,
+            config,
+        })
+    }
+
+    /// Set up ARF storage for the repository at `repo`.
+    ///
+    /// For branch storage this creates the orphan `arf` branch, and mounts it
+    /// at `.arf` in the main checkout when `checkout` is set, the repository
+    /// has a working tree, and no worktree has the branch checked out yet.
+    /// For notes storage it configures git to carry `refs/notes/arf` across
+    /// rebases and amends.
+    ///
+    /// Returns `None` if ARF was already set up this way.
+    pub fn init(
+        repo: impl Into<PathBuf>,
+        storage: Storage,
+        checkout: bool,
+    ) -> Result<Option<Self>> {
+        let repo = repo.into();
+
+        if !git::is_repo(&repo) {
+            return Err(anyhow!("Not a git repository. Run 'git init' first."));
+        }
+
+        let current = Self::open(&repo)?;
+        let branch = current.config.branch.clone();
+        let already = match storage {
+            Storage::Branch => git::ref_sha(&repo, &current.config.branch_ref()).is_some(),
+            Storage::Notes => current.storage() == Storage::Notes,
+        };
+        if already && current.storage() == storage {
+            return Ok(None);
+        }
+
+        git::run(&repo, &["config", "arf.storage", &storage.to_string()])?;
+
+        match storage {
+            Storage::Branch => {
+                if !already {
+                    Objects::new(&repo, &branch, &current.config.remote).commit(
+                        &[("README.md".to_string(), Some(README.to_string()))],
+                        "Initialize ARF",
+                    )?;
+                }
+
+                // Mount in the main checkout, which outlives linked worktrees
+                let mount = git::worktrees(&repo)
+                    .into_iter()
+                    .next()
+                    .filter(|w| !w.bare)
+                    .map(|w| w.path.join(".arf"))
+                    .filter(|dir| !dir.exists());
+                if let (true, Some(dir), None) = (checkout, mount, current.worktree()) {
+                    let dir = dir.to_string_lossy();
+                    let output = git::output(&repo, &["worktree", "add", &dir, &branch])?;
+
+                    if !output.status.success() {
+                        return Err(anyhow!(
+                            "Failed to mount ARF branch: {}",
+                            String::from_utf8_lossy(&output.stderr)
+                        ));
+                    }
+                }
+            }
+            Storage::Notes => {
+                // Let rebase and amend carry notes over to the rewritten commits
+                let rewrite_refs =
+                    git::output(&repo, &["config", "--get-all", "notes.rewriteRef"])?;
+                let configured = String::from_utf8_lossy(&rewrite_refs.stdout)
+                    .lines()
+                    .any(|l| l.trim() == NOTES_REF);
+                if !configured {
+                    git::run(&repo, &["config", "--add", "notes.rewriteRef", NOTES_REF])?;
+                }
+            }
+        }
+
+        Self::open(repo).map(Some)
+    }
+
+    /// The repository this store belongs to.
+    pub fn repo(&self) -> &Path {
+        &self.repo
+    }
+
+    /// The worktree with the `arf` branch checked out, if records are stored
+    /// through one.
+    pub fn worktree(&self) -> Option<PathBuf> {
+        self.worktree.clone()
+    }
+
+    pub(crate) fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    pub(crate) fn notes(&self) -> &Notes {
+        &self.notes
+    }
+
+    /// Settings the store was opened with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Where records are kept.
+    pub fn storage(&self) -> Storage {
+        self.config.storage
+    }
+
+    /// Whether ARF has been set up: notes storage is configured, or the ARF
+    /// branch exists locally or as a tracking ref of the configured remote.
+    pub fn is_initialized(&self) -> bool {
+        self.storage() == Storage::Notes || self.backend.exists()
+    }
+
+    /// Every record in the store, in no particular order.
+    ///
+    /// Files that fail to parse are skipped.
+    pub fn list(&self) -> Result<Vec<StoredRecord>> {
+        if self.storage() == Storage::Notes {
+            let mut records = Vec::new();
+            for (commit, content) in self.notes.read_all()? {
+                records.extend(note_records(&commit, &content));
+            }
+            return self.dedupe_notes(records);
+        }
+
+        Ok(self
+            .backend
+            .read_all("records")?
+            .into_iter()
+            .filter(|(path, _)| path.ends_with(".toml"))
+            .flat_map(|(path, content)| file_records(&path, &content))
+            .collect())
+    }
+
+    /// git copies notes to rewritten commits but leaves the originals on
+    /// the old ones, so without the post-rewrite hook a record turns up once
+    /// per rewrite. Keep one copy of each ID, preferring a reachable commit.
+    fn dedupe_notes(&self, records: Vec<StoredRecord>) -> Result<Vec<StoredRecord>> {
+        let mut seen = HashSet::new();
+        if records
+            .iter()
+            .filter_map(|s| s.record.id.as_deref())
+            .all(|id| seen.insert(id))
+        {
+            return Ok(records);
+        }
+
+        let reachable = self.reachable_commits()?;
+        let live = |s: &StoredRecord| {
+            s.record
+                .commit
+                .as_deref()
+                .is_some_and(|c| reachable.contains(c))
+        };
+        let mut kept: Vec<StoredRecord> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for stored in records {
+            let Some(id) = stored.record.id.clone() else {
+                kept.push(stored);
+                continue;
+            };
+            match index.get(&id) {
+                Some(&i) => {
+                    if !live(&kept[i]) && live(&stored) {
+                        kept[i] = stored;
+                    }
+                }
+                None => {
+                    index.insert(id, kept.len());
+                    kept.push(stored);
+                }
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Every commit reachable from a branch, tag, or remote ref, excluding
+    /// the ARF branch and notes themselves.
+    pub fn reachable_commits(&self) -> Result<HashSet<String>> {
+        let out = git::run(
+            &self.repo,
+            &[
+                "rev-list",
+                "--exclude=refs/notes/*",
+                &format!("--exclude={}", self.config.branch_ref()),
+                &format!("--exclude={}", self.config.remote_ref("*")),
+                "--all",
+            ],
+        )?;
+        Ok(out.lines().map(|l| l.to_string()).collect())
+    }
+
+    /// Records attached to `commit`, oldest first.
+    pub fn load(&self, commit: &str) -> Result<Vec<StoredRecord>> {
+        let sha = git::resolve_commit(&self.repo, commit)?;
+        Ok(self.load_resolved(&sha))
+    }
+
+    /// Records attached to a full commit SHA, oldest first.
+    pub fn load_resolved(&self, sha: &str) -> Vec<StoredRecord> {
+        if self.storage() == Storage::Notes {
+            let mut records = match self.notes.read(sha) {
+                Ok(Some(content)) => note_records(sha, &content),
+                _ => Vec::new(),
+            };
+            records.sort_by(|a, b| a.record.timestamp.cmp(&b.record.timestamp));
+            return records;
+        }
+
+        let mut records: Vec<StoredRecord> = self
+            .commit_dirs(sha)
+            .iter()
+            .flat_map(|dir| self.backend.read_all(dir).unwrap_or_default())
+            .filter(|(path, _)| path.ends_with(".toml"))
+            .flat_map(|(path, content)| file_records(&path, &content))
+            .collect();
+
+        records.sort_by(|a, b| a.record.timestamp.cmp(&b.record.timestamp));
+        records
+    }
+
+    /// Records matching `query`, newest first.
+    pub fn query(&self, query: &Query) -> Result<Vec<StoredRecord>> {
+        let mut records = match query.commit {
+            Some(ref commit) => self.load(commit)?,
+            None => self.list()?,
+        };
+        records.retain(|stored| query.matches(&stored.record));
+
+        if query.range.is_some() || !query.paths.is_empty() {
+            let commits = self.commits_in(query.range.as_deref(), &query.paths)?;
+            records.retain(|stored| {
+                stored.record.commit.as_deref().is_some_and(|c| {
+                    commits.contains(c)
+                        || (!git::is_full_sha(c) && commits.iter().any(|sha| sha.starts_with(c)))
+                })
+            });
+        }
+
+        records.sort_by(|a, b| b.record.timestamp.cmp(&a.record.timestamp));
+        if let Some(limit) = query.limit {
+            records.truncate(limit);
+        }
+        Ok(records)
+    }
+
+    /// Write a record and commit it to the `arf` branch.
+    ///
+    /// The record is attached to its `commit` field, or to HEAD if unset.
+    pub fn write(&self, record: ArfRecord) -> Result<StoredRecord> {
+        let message = format!("Record: {}", record.what);
+        let mut stored = self.write_all(vec![record], &message)?;
+        Ok(stored.remove(0))
+    }
+
+    /// Write several records in one commit to the `arf` branch (or one note
+    /// update per annotated commit). Each is attached to its `commit` field,
+    /// or to HEAD if unset.
+    pub fn write_all(&self, records: Vec<ArfRecord>, message: &str) -> Result<Vec<StoredRecord>> {
+        let mut resolved = Vec::new();
+        for mut record in records {
+            let sha = match record.commit {
+                Some(ref c) => git::resolve_commit(&self.repo, c)?,
+                None => git::head(&self.repo)?,
+            };
+            record.commit = Some(sha);
+            resolved.push(record);
+        }
+        self.attach(resolved, message)
+    }
+
+    /// Store records whose `commit` fields hold full SHAs.
+    fn attach(&self, records: Vec<ArfRecord>, message: &str) -> Result<Vec<StoredRecord>> {
+        let sha_of = |record: &ArfRecord| record.commit.clone().unwrap_or_default();
+
+        if self.storage() == Storage::Notes {
+            let mut by_commit: BTreeMap<String, Vec<ArfRecord>> = BTreeMap::new();
+            for record in &records {
+                by_commit
+                    .entry(sha_of(record))
+                    .or_default()
+                    .push(record.clone());
+            }
+            for (sha, new) in by_commit {
+                let mut existing: Vec<ArfRecord> = self
+                    .load_resolved(&sha)
+                    .into_iter()
+                    .map(|r| r.record)
+                    .collect();
+                existing.extend(new);
+                self.notes
+                    .write(&sha, &ArfRecord::to_toml_stream(&existing)?)?;
+            }
+
+            return Ok(records
+                .into_iter()
+                .map(|record| StoredRecord {
+                    path: PathBuf::from(sha_of(&record)),
+                    record,
+                })
+                .collect());
+        }
+
+        let mut changes: Vec<Change> = Vec::new();
+        let mut stored = Vec::new();
+        for record in records {
+            let path = format!("records/{}/{}.toml", sha_of(&record), record.name());
+            changes.push((path.clone(), Some(record.to_toml()?)));
+            stored.push(StoredRecord {
+                path: PathBuf::from(path),
+                record,
+            });
+        }
+        self.commit(&changes, message)?;
+
+        Ok(stored)
+    }
+
+    /// Directory holding records staged with [`RecordStore::stage`]. It is
+    /// inside this worktree's git directory, so pending records never leave
+    /// the machine and each worktree has its own.
+    pub fn pending_dir(&self) -> Result<PathBuf> {
+        let git_dir = PathBuf::from(git::run(&self.repo, &["rev-parse", "--git-dir"])?);
+        let git_dir = if git_dir.is_absolute() {
+            git_dir
+        } else {
+            self.repo.join(git_dir)
+        };
+        Ok(git_dir.join("arf").join("pending"))
+    }
+
+    /// Stage a record for the next commit instead of attaching it now.
+    /// [`RecordStore::bind_pending`] attaches it once the commit exists.
+    pub fn stage(&self, mut record: ArfRecord) -> Result<PathBuf> {
+        record.commit = None;
+        let dir = self.pending_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.toml", record.name()));
+        std::fs::write(&path, record.to_toml()?)?;
+        Ok(path)
+    }
+
+    /// Records staged for the next commit, oldest first.
+    pub fn pending(&self) -> Result<Vec<(PathBuf, ArfRecord)>> {
+        let Ok(entries) = std::fs::read_dir(self.pending_dir()?) else {
+            return Ok(Vec::new());
+        };
+
+        let mut pending = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)?;
+            let record = ArfRecord::from_toml(&content)
+                .map_err(|e| anyhow!("{}: {}", path.display(), e.message()))?;
+            pending.push((path, record));
+        }
+        pending.sort_by(|a, b| a.1.timestamp.cmp(&b.1.timestamp));
+        Ok(pending)
+    }
+
+    /// Attach every pending record to `commit` and clear the staging area.
+    pub fn bind_pending(&self, commit: &str) -> Result<Vec<StoredRecord>> {
+        let pending = self.pending()?;
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sha = git::resolve_commit(&self.repo, commit)?;
+        let (paths, records): (Vec<PathBuf>, Vec<ArfRecord>) = pending
+            .into_iter()
+            .map(|(path, mut record)| {
+                record.commit = Some(sha.clone());
+                (path, record)
+            })
+            .unzip();
+        let message = match records.as_slice() {
+            [record] => format!("Record: {}", record.what),
+            _ => format!("Record {} pending records", records.len()),
+        };
+        let stored = self.attach(records, &message)?;
+
+        for path in paths {
+            std::fs::remove_file(path)?;
+        }
+        Ok(stored)
+    }
+
+    /// Find a single record by ID or unique ID prefix (case-insensitive).
+    ///
+    /// Records written before IDs existed are found by their name
+    /// (`<agent>-<YYYYmmdd-HHMMSS>`) or path on the `arf` branch.
+    pub fn find(&self, reference: &str) -> Result<StoredRecord> {
+        let reference = reference.trim_end_matches(".toml");
+        let records = self.list()?;
+
+        let exact = |stored: &StoredRecord| {
+            stored.record.name().eq_ignore_ascii_case(reference)
+                || stored.path.to_string_lossy().trim_end_matches(".toml") == reference
+        };
+        let prefix = |stored: &StoredRecord| {
+            stored.record.id.as_deref().is_some_and(|id| {
+                id.get(..reference.len())
+                    .is_some_and(|p| p.eq_ignore_ascii_case(reference))
+            })
+        };
+
+        let mut matches: Vec<StoredRecord> = records.iter().filter(|s| exact(s)).cloned().collect();
+        if matches.is_empty() {
+            matches = records.into_iter().filter(|s| prefix(s)).collect();
+        }
+
+        match matches.len() {
+            0 => Err(anyhow!("No record with ID '{}'", reference)),
+            1 => Ok(matches.remove(0)),
+            n => Err(anyhow!(
+                "'{}' matches {} records:\n  {}",
+                reference,
+                n,
+                matches
+                    .iter()
+                    .map(|m| format!(
+                        "{} {} ({})",
+                        m.record.name(),
+                        m.record.short_commit(),
+                        m.record.what
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("\n  ")
+            )),
+        }
+    }
+
+    /// Replace a stored record in place and commit the change.
+    pub fn update(
+        &self,
+        stored: &StoredRecord,
+        record: ArfRecord,
+        message: &str,
+    ) -> Result<StoredRecord> {
+        if self.storage() == Storage::Notes {
+            let sha = stored.path.to_string_lossy().to_string();
+            let mut records: Vec<ArfRecord> = self
+                .load_resolved(&sha)
+                .into_iter()
+                .map(|r| r.record)
+                .collect();
+            let Some(slot) = records.iter_mut().find(|r| **r == stored.record) else {
+                return Err(anyhow!("Record changed on disk: {}", stored.record.what));
+            };
+            *slot = record.clone();
+            self.notes
+                .write(&sha, &ArfRecord::to_toml_stream(&records)?)?;
+        } else {
+            let path = stored.path.to_string_lossy().to_string();
+            // A stream file keeps its other records
+            let mut records = self
+                .backend
+                .read(&path)?
+                .and_then(|content| ArfRecord::from_toml_file(&content).ok())
+                .unwrap_or_default();
+            let content = if records.len() > 1 {
+                let Some(slot) = records.iter_mut().find(|r| **r == stored.record) else {
+                    return Err(anyhow!("Record changed on disk: {}", stored.record.what));
+                };
+                *slot = record.clone();
+                ArfRecord::to_toml_stream(&records)?
+            } else {
+                record.to_toml()?
+            };
+            self.commit(&[(path, Some(content))], message)?;
+        }
+
+        Ok(StoredRecord {
+            path: stored.path.clone(),
+            record,
+        })
+    }
+
+    /// Re-attach records after commits were rewritten (amend, rebase,
+    /// cherry-pick).
+    ///
+    /// `pairs` maps old commit SHAs to new ones, as git passes them to the
+    /// `post-rewrite` hook. Records move to the new commit, or are copied
+    /// when `copy` is set. Returns the number of records re-attached.
+    pub fn rewrite(&self, pairs: &[(String, String)], copy: bool) -> Result<usize> {
+        let mut count = 0;
+        let mut changes: Vec<Change> = Vec::new();
+
+        for (old, new) in pairs {
+            if old == new {
+                continue;
+            }
+
+            let moved = self.load_resolved(old);
+            if moved.is_empty() {
+                continue;
+            }
+            count += moved.len();
+
+            if self.storage() == Storage::Notes {
+                // git may already have copied the note (notes.rewriteRef);
+                // note_records drops the duplicates that leaves behind.
+                let mut records: Vec<ArfRecord> = self
+                    .load_resolved(new)
+                    .into_iter()
+                    .map(|r| r.record)
+                    .collect();
+                for stored in moved {
+                    let mut record = stored.record;
+                    record.commit = Some(new.clone());
+                    if records.contains(&record) {
+                        continue;
+                    }
+                    if copy {
+                        record.id = Some(id::generate());
+                    }
+                    records.push(record);
+                }
+                self.notes
+                    .write(new, &ArfRecord::to_toml_stream(&records)?)?;
+                if !copy {
+                    self.notes.write(old, "")?;
+                }
+                continue;
+            }
+
+            for stored in &moved {
+                let path = stored.path.to_string_lossy().to_string();
+                let mut file_name = path.rsplit('/').next().unwrap_or(&path).to_string();
+                let mut record = stored.record.clone();
+                record.commit = Some(new.clone());
+                // A copy is a separate record, so it gets its own ID. Records
+                // from a stream file are split into files of their own.
+                let shared = moved.iter().filter(|s| s.path == stored.path).count() > 1;
+                if copy {
+                    record.id = Some(id::generate());
+                }
+                if copy || shared {
+                    file_name = format!("{}.toml", record.name());
+                }
+
+                changes.push((
+                    format!("records/{}/{}", new, file_name),
+                    Some(record.to_toml()?),
+                ));
+                if !copy && !changes.iter().any(|(p, _)| *p == path) {
+                    changes.push((path, None));
+                }
+            }
+        }
+
+        if !changes.is_empty() {
+            let verb = if copy { "Copy" } else { "Move" };
+            self.commit(
+                &changes,
+                &format!("{} {} record(s) to rewritten commits", verb, count),
+            )?;
+        }
+
+        Ok(count)
+    }
+
+    /// Commits in `range` (every ref outside ARF if `None`) that touched one
+    /// of `paths` (any change if empty).
+    fn commits_in(&self, range: Option<&str>, paths: &[String]) -> Result<HashSet<String>> {
+        let branch_exclude = format!("--exclude={}", self.config.branch_ref());
+        let remote_exclude = format!("--exclude={}", self.config.remote_ref("*"));
+        let mut args = vec!["rev-list"];
+        match range {
+            Some(range) => args.extend(range.split_whitespace()),
+            None => args.extend([
+                "--exclude=refs/notes/*",
+                branch_exclude.as_str(),
+                remote_exclude.as_str(),
+                "--all",
+            ]),
+        }
+        args.push("--");
+        args.extend(paths.iter().map(|p| p.as_str()));
+
+        let out = git::run(&self.repo, &args)?;
+        Ok(out.lines().map(|l| l.to_string()).collect())
+    }
+
+    /// Names of all specs, sorted.
+    pub fn specs(&self) -> Result<Vec<String>> {
+        let mut specs: Vec<String> = self
+            .backend
+            .list("specs")?
+            .iter()
+            .filter_map(|path| path.strip_prefix("specs/")?.strip_suffix(".arf"))
+            .filter(|name| !name.contains('/'))
+            .map(|name| name.to_string())
+            .collect();
+
+        specs.sort();
+        Ok(specs)
+    }
+
+    /// Raw contents of the spec called `name`.
+    pub fn spec(&self, name: &str) -> Result<String> {
+        self.backend
+            .read(&format!("specs/{}.arf", name))?
+            .ok_or_else(|| anyhow!("Spec not found: {}", name))
+    }
+
+    /// Write the spec called `name`, creating or replacing it.
+    pub fn save_spec(&self, name: &str, content: &str, message: &str) -> Result<()> {
+        self.commit(
+            &[(format!("specs/{}.arf", name), Some(content.to_string()))],
+            message,
+        )
+    }
+
+    /// Apply file changes on the `arf` branch as a single commit.
+    pub fn commit(&self, changes: &[Change], message: &str) -> Result<()> {
+        self.backend.commit(changes, message)
+    }
+
+    /// Move records out of short-SHA directories into full-SHA ones.
+    ///
+    /// Older versions keyed `records/` by an 8-character prefix (or whatever
+    /// `--commit` was given). Each record moves to the directory of the
+    /// commit its `commit` field names, falling back to resolving the
+    /// directory name; its `commit` field is rewritten to the full SHA.
+    /// Directories that can't be resolved unambiguously are left alone.
+    pub fn migrate(&self, dry_run: bool) -> Result<Migration> {
+        let mut migration = Migration::default();
+        if self.storage() == Storage::Notes {
+            return Ok(migration);
+        }
+
+        let mut changes: Vec<Change> = Vec::new();
+
+        for dir in self.backend.children("records")? {
+            if git::is_full_sha(&dir) {
+                continue;
+            }
+
+            let dir_sha = git::resolve_commit(&self.repo, &dir)
+                .ok()
+                .filter(|sha| sha.starts_with(dir.as_str()));
+
+            let prefix = format!("records/{}/", dir);
+            for (path, content) in self.backend.read_all(&prefix)? {
+                if !path.starts_with(&prefix) {
+                    continue;
+                }
+                let Some(file_name) = path.rsplit('/').next().map(|s| s.to_string()) else {
+                    continue;
+                };
+
+                let mut records = match ArfRecord::from_toml_file(&content) {
+                    Ok(records) if !records.is_empty() => records,
+                    _ => {
+                        migration
+                            .skipped
+                            .push((path, "failed to parse".to_string()));
+                        continue;
+                    }
+                };
+
+                // Prefer the record's own commit field when it agrees with the directory
+                let field_sha = records[0]
+                    .commit
+                    .as_deref()
+                    .and_then(|c| git::resolve_commit(&self.repo, c).ok())
+                    .filter(|sha| sha.starts_with(dir.as_str()));
+
+                let Some(sha) = field_sha.or_else(|| dir_sha.clone()) else {
+                    let candidates = git::commits_with_prefix(&self.repo, &dir)?;
+                    let reason = if candidates.len() > 1 {
+                        format!("prefix '{}' matches {} commits", dir, candidates.len())
+                    } else {
+                        format!("'{}' does not name a commit", dir)
+                    };
+                    migration.skipped.push((path, reason));
+                    continue;
+                };
+
+                for record in &mut records {
+                    record.commit = Some(sha.clone());
+                }
+                let content = ArfRecord::to_toml_file(&records)?;
+                let new_path = format!("records/{}/{}", sha, file_name);
+                changes.push((path.clone(), None));
+                changes.push((new_path.clone(), Some(content)));
+                migration.moved.push((path, new_path));
+            }
+        }
+
+        if !dry_run && !changes.is_empty() {
+            self.commit(&changes, "Migrate records to full commit SHAs")?;
+        }
+
+        Ok(migration)
+    }
+
+    /// Record directories for a full commit SHA.
+    ///
+    /// Records live under `records/<full sha>/`. Directories named by a short
+    /// prefix (from before `arf migrate`) are included only when that prefix
+    /// resolves to exactly this commit.
+    fn commit_dirs(&self, sha: &str) -> Vec<String> {
+        let Ok(children) = self.backend.children("records") else {
+            return Vec::new();
+        };
+
+        children
+            .into_iter()
+            .filter(|name| {
+                name == sha
+                    || (sha.starts_with(name.as_str())
+                        && !git::is_full_sha(name)
+                        && git::resolve_commit(&self.repo, name).is_ok_and(|s| s == sha))
+            })
+            .map(|name| format!("records/{}", name))
+            .collect()
+    }
+}
+
+/// Result of [`RecordStore::migrate`].
+#[derive(Debug, Default)]
+pub struct Migration {
+    /// Records moved, as `(old path, new path)`.
+    pub moved: Vec<(String, String)>,
+    /// Records left in place, as `(path, reason)`.
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Parse the records in a file on the `arf` branch: one record, or a TOML
+/// stream of several. Files that fail to parse yield nothing.
+pub(crate) fn file_records(path: &str, content: &str) -> Vec<StoredRecord> {
+    ArfRecord::from_toml_file(content)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|record| StoredRecord {
+            path: PathBuf::from(path),
+            record,
+        })
+        .collect()
+}
+
+/// Parse the records in a note, dropping exact duplicates left by a union
+/// merge of notes that both sides had already.
+///
+/// The annotated commit is authoritative: notes carried over by a rebase or
+/// amend still have the old SHA in their `commit` field.
+pub(crate) fn note_records(commit: &str, content: &str) -> Vec<StoredRecord> {
+    let mut records: Vec<ArfRecord> = Vec::new();
+    for mut record in ArfRecord::from_toml_stream(content).unwrap_or_default() {
+        record.commit = Some(commit.to_string());
+        if !records.contains(&record) {
+            records.push(record);
+        }
+    }
+
+    records
+        .into_iter()
+        .map(|record| StoredRecord {
+            path: PathBuf::from(commit),
+            record,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_dates() {
+        let date = parse_time("2026-01-31").unwrap();
+        assert_eq!(date.to_rfc3339(), "2026-01-31T00:00:00+00:00");
+
+        let time = parse_time("2026-01-31T12:30:00+02:00").unwrap();
+        assert_eq!(time.to_rfc3339(), "2026-01-31T10:30:00+00:00");
+    }
+
+    #[test]
+    fn parse_time_ages() {
+        let before = Utc::now();
+        let week = parse_time("1w").unwrap();
+        let after = Utc::now();
+        assert!(before - Duration::weeks(1) <= week && week <= after - Duration::weeks(1));
+
+        assert!(parse_time("30m").unwrap() > parse_time("12h").unwrap());
+        assert!(parse_time("yesterday").unwrap() < parse_time("today").unwrap());
+    }
+
+    #[test]
+    fn parse_time_rejects_unknown_units() {
+        let err = parse_time("7y").unwrap_err().to_string();
+        assert!(err.contains("Unknown time unit"), "{}", err);
+    }
+
+    #[test]
+    fn parse_time_rejects_non_ascii_without_panicking() {
+        let err = parse_time("7é").unwrap_err().to_string();
+        assert!(err.contains("Unknown time unit"), "{}", err);
+        assert!(parse_time("é").is_err());
+        assert!(parse_time("日d").is_err());
+    }
+
+    #[test]
+    fn parse_time_rejects_garbage() {
+        assert!(parse_time("").is_err());
+        assert!(parse_time("d").is_err());
+        assert!(parse_time("soon").is_err());
+        assert!(parse_time("99999999999999w").is_err());
+    }
+}
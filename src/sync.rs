@@ -0,0 +1,479 @@
+//! Pulling another clone's records into ours (`arf sync`).
+//!
+//! Records are append-only files, one per record, so two clones that both
+//! recorded reasoning have touched different paths. Pulling merges the two
+//! branch tips file by file, without a checkout: every path changed on only
+//! one side is taken from that side. Only a path changed differently on both
+//! sides (the same record edited, retracted, or given an outcome twice) is a
+//! conflict. Conflicts stop the pull before anything is written, unless the
+//! caller says which side to prefer.
+//!
+//! Notes storage keeps all of a commit's records in one note, so the merge
+//! goes record by record instead: a record changed on one side takes that
+//! side's version, and one changed differently on both sides is a conflict,
+//! settled the same way.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::backend::Objects;
+use crate::git;
+use crate::record::ArfRecord;
+use crate::store::{RecordStore, Storage};
+use crate::NOTES_REF;
+
+/// Which side wins a conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefer {
+    /// Keep our version.
+    Local,
+    /// Take the remote's version.
+    Remote,
+}
+
+impl FromStr for Prefer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "local" | "ours" => Ok(Prefer::Local),
+            "remote" | "theirs" => Ok(Prefer::Remote),
+            _ => Err(anyhow!("Unknown side '{}' (expected local or remote)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Prefer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Prefer::Local => "local",
+            Prefer::Remote => "remote",
+        })
+    }
+}
+
+/// A file changed differently on both sides.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// Path on the `arf` branch, or `note on <sha>` for notes storage.
+    pub path: String,
+    /// Our version, or `None` if we deleted it.
+    pub local: Option<String>,
+    /// The remote's version, or `None` if it was deleted there.
+    pub remote: Option<String>,
+}
+
+impl Conflict {
+    /// The record this is about: its ID, or the file name if neither side
+    /// parses.
+    pub fn record_name(&self) -> String {
+        [&self.local, &self.remote]
+            .into_iter()
+            .flatten()
+            .find_map(|content| ArfRecord::from_toml(content).ok())
+            .map(|record| record.name())
+            .unwrap_or_else(|| {
+                let name = self.path.rsplit('/').next().unwrap_or(&self.path);
+                name.trim_end_matches(".toml").to_string()
+            })
+    }
+}
+
+/// What a pull did.
+#[derive(Debug, Clone)]
+pub enum Pulled {
+    /// The remote has no ARF branch (or notes) yet.
+    NoRemote,
+    /// Nothing new on the remote.
+    UpToDate,
+    /// The local branch was behind and moved forward.
+    FastForward,
+    /// Both sides had new records; they were merged. Counts the changes
+    /// taken from the remote (conflicts settled its way included), and the
+    /// conflicts settled by a preferred side.
+    Merged { taken: usize, resolved: usize },
+    /// Both sides changed the same files and no side was preferred. Nothing
+    /// was written.
+    Conflicts(Vec<Conflict>),
+}
+
+/// Fetch `remote`'s ARF branch into `refs/remotes/<remote>/<branch>` and
+/// merge it into ours.
+pub fn pull_branch(store: &RecordStore, remote: &str, prefer: Option<Prefer>) -> Result<Pulled> {
+    let repo = store.repo();
+    let config = store.config();
+    let tracking = config.remote_ref(remote);
+    let refspec = format!("+{}:{}", config.branch_ref(), tracking);
+    if !fetch(store, remote, &refspec)? {
+        return Ok(Pulled::NoRemote);
+    }
+    let Some(theirs) = git::ref_sha(repo, &tracking) else {
+        return Ok(Pulled::NoRemote);
+    };
+
+    let Some(ours) = git::ref_sha(repo, &config.branch_ref()) else {
+        advance(store, None, &theirs)?;
+        return Ok(Pulled::FastForward);
+    };
+
+    if is_ancestor(store, &theirs, &ours) {
+        return Ok(Pulled::UpToDate);
+    }
+    if is_ancestor(store, &ours, &theirs) {
+        advance(store, Some(&ours), &theirs)?;
+        return Ok(Pulled::FastForward);
+    }
+
+    // Diverged: take every file only they changed, and check the rest
+    let base = git::output(repo, &["merge-base", &ours, &theirs])?;
+    let base = String::from_utf8_lossy(&base.stdout).trim().to_string();
+    let base = (!base.is_empty()).then_some(base);
+    let our_changes = changed_blobs(store, base.as_deref(), &ours)?;
+    let their_changes = changed_blobs(store, base.as_deref(), &theirs)?;
+
+    let mut blobs = Vec::new();
+    let mut conflicts = Vec::new();
+    for (path, theirs_blob) in &their_changes {
+        match our_changes.get(path) {
+            None => blobs.push((path.clone(), theirs_blob.clone())),
+            Some(ours_blob) if ours_blob == theirs_blob => {}
+            Some(ours_blob) => {
+                conflicts.push((path.clone(), ours_blob.clone(), theirs_blob.clone()))
+            }
+        }
+    }
+    let resolved = conflicts.len();
+    match prefer {
+        None if !conflicts.is_empty() => {
+            let read = |blob: &Option<String>| -> Result<Option<String>> {
+                match blob {
+                    Some(oid) => Ok(git::read_blobs(repo, &[oid.as_str()])?.pop()),
+                    None => Ok(None),
+                }
+            };
+            let mut reported = Vec::new();
+            for (path, ours_blob, theirs_blob) in &conflicts {
+                reported.push(Conflict {
+                    path: path.clone(),
+                    local: read(ours_blob)?,
+                    remote: read(theirs_blob)?,
+                });
+            }
+            return Ok(Pulled::Conflicts(reported));
+        }
+        Some(Prefer::Remote) => {
+            blobs.extend(
+                conflicts
+                    .into_iter()
+                    .map(|(path, _, theirs)| (path, theirs)),
+            );
+        }
+        _ => {}
+    }
+    let taken = blobs.len();
+
+    let our_tree = git::run(repo, &["rev-parse", &format!("{}^{{tree}}", ours)])?;
+    let tree = Objects::new(repo, &config.branch, remote).tree_with(&our_tree, &blobs)?;
+    let message = format!("Merge ARF records from {}", remote);
+    let merge = git::run(
+        repo,
+        &[
+            "commit-tree",
+            &tree,
+            "-p",
+            &ours,
+            "-p",
+            &theirs,
+            "-m",
+            &message,
+        ],
+    )?;
+    advance(store, Some(&ours), &merge)?;
+
+    Ok(Pulled::Merged { taken, resolved })
+}
+
+/// Fetch `remote`'s ARF notes and merge them into ours, record by record.
+pub fn pull_notes(store: &RecordStore, remote: &str, prefer: Option<Prefer>) -> Result<Pulled> {
+    let repo = store.repo();
+    let tracking = NOTES_REF.replacen("refs/notes/", &format!("refs/notes/remotes/{}/", remote), 1);
+    let refspec = format!("+{}:{}", NOTES_REF, tracking);
+    if !fetch(store, remote, &refspec)? {
+        return Ok(Pulled::NoRemote);
+    }
+    let Some(theirs) = git::ref_sha(repo, &tracking) else {
+        return Ok(Pulled::NoRemote);
+    };
+
+    let ours = git::ref_sha(repo, NOTES_REF);
+    if let Some(ref ours) = ours {
+        if is_ancestor(store, &theirs, ours) {
+            return Ok(Pulled::UpToDate);
+        }
+    }
+    let Some(ours) = ours.filter(|ours| !is_ancestor(store, ours, &theirs)) else {
+        merge_notes(store, remote, &tracking)?;
+        return Ok(Pulled::FastForward);
+    };
+
+    // Diverged: work out each note both sides changed before writing anything
+    let base = git::output(repo, &["merge-base", &ours, &theirs])?;
+    let base = String::from_utf8_lossy(&base.stdout).trim().to_string();
+    let base_notes = match base.as_str() {
+        "" => BTreeMap::new(),
+        base => notes_at(store, base)?,
+    };
+    let our_notes = notes_at(store, &ours)?;
+    let their_notes = notes_at(store, &theirs)?;
+
+    let mut taken = 0;
+    let mut conflicts = Vec::new();
+    let mut rewrites: Vec<(String, Vec<ArfRecord>)> = Vec::new();
+    for (commit, their_content) in &their_notes {
+        let base_content = base_notes.get(commit);
+        let Some(our_content) = our_notes.get(commit) else {
+            // Ours moved away (a rewrite); don't let the union bring it back
+            if base_content == Some(their_content) {
+                rewrites.push((commit.clone(), Vec::new()));
+            } else {
+                taken += 1;
+            }
+            continue;
+        };
+        if our_content == their_content {
+            continue;
+        }
+
+        let parse = |c: &str| ArfRecord::from_toml_stream(c).unwrap_or_default();
+        let base_records = base_content.map(|c| parse(c)).unwrap_or_default();
+        let mut records = parse(our_content);
+        for their in parse(their_content) {
+            let same = |r: &ArfRecord| match (&r.id, &their.id) {
+                (Some(a), Some(b)) => a == b,
+                _ => *r == their,
+            };
+            let Some(i) = records.iter().position(same) else {
+                records.push(their);
+                taken += 1;
+                continue;
+            };
+            let ours = &records[i];
+            let base = base_records.iter().find(|r| same(r));
+            if *ours == their || base == Some(&their) {
+                continue;
+            }
+            if base == Some(ours) {
+                records[i] = their;
+                taken += 1;
+                continue;
+            }
+            conflicts.push(Conflict {
+                path: format!("note on {}", git::short_sha(commit)),
+                local: Some(ours.to_toml()?),
+                remote: Some(their.to_toml()?),
+            });
+            if prefer == Some(Prefer::Remote) {
+                records[i] = their;
+                taken += 1;
+            }
+        }
+        rewrites.push((commit.clone(), records));
+    }
+
+    if prefer.is_none() && !conflicts.is_empty() {
+        return Ok(Pulled::Conflicts(conflicts));
+    }
+
+    // The union merge joins the histories (so the next push fast-forwards);
+    // then each note both sides changed gets its merged records
+    merge_notes(store, remote, &tracking)?;
+    for (commit, records) in &rewrites {
+        let content = if records.is_empty() {
+            String::new()
+        } else {
+            ArfRecord::to_toml_stream(records)?
+        };
+        store.notes().write(commit, &content)?;
+    }
+
+    Ok(Pulled::Merged {
+        taken,
+        resolved: conflicts.len(),
+    })
+}
+
+fn merge_notes(store: &RecordStore, remote: &str, tracking: &str) -> Result<()> {
+    let output = git::output(
+        store.repo(),
+        &[
+            "notes", "--ref", NOTES_REF, "merge", "-s", "union", tracking,
+        ],
+    )?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "Merging notes from {} failed: {}",
+            remote,
+            stderr.trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Every note in the notes commit `commit`, keyed by annotated commit.
+fn notes_at(store: &RecordStore, commit: &str) -> Result<BTreeMap<String, String>> {
+    let repo = store.repo();
+    // Paths are the annotated SHA, fanned out into directories ("ab/cdef...")
+    let out = git::run(repo, &["ls-tree", "-r", &format!("{}^{{tree}}", commit)])?;
+    let entries: Vec<(String, String)> = out
+        .lines()
+        .filter_map(|line| {
+            let (meta, path) = line.split_once('\t')?;
+            let oid = meta.split(' ').nth(2)?;
+            Some((path.replace('/', ""), oid.to_string()))
+        })
+        .collect();
+    let blobs: Vec<&str> = entries.iter().map(|(_, oid)| oid.as_str()).collect();
+    let contents = git::read_blobs(repo, &blobs)?;
+    Ok(entries
+        .into_iter()
+        .map(|(annotated, _)| annotated)
+        .zip(contents)
+        .collect())
+}
+
+/// Push the local ARF branch (or notes) to `remote`.
+pub fn push(store: &RecordStore, remote: &str) -> Result<Pushed> {
+    let refspec = match store.storage() {
+        Storage::Branch => format!("{0}:{0}", store.config().branch_ref()),
+        Storage::Notes => format!("{0}:{0}", NOTES_REF),
+    };
+    let output = git::output(store.repo(), &["push", "--porcelain", remote, &refspec])?;
+    if output.status.success() {
+        return Ok(Pushed::Done);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stdout
+        .lines()
+        .any(|l| l.starts_with('!') && l.contains("rejected"))
+    {
+        return Ok(Pushed::Rejected);
+    }
+    Err(anyhow!("Pushing to {} failed: {}", remote, stderr.trim()))
+}
+
+/// What a push did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pushed {
+    Done,
+    /// The remote has records we don't; pull first.
+    Rejected,
+}
+
+/// Every remote configured in the repository.
+pub fn remotes(store: &RecordStore) -> Result<Vec<String>> {
+    Ok(git::run(store.repo(), &["remote"])?
+        .lines()
+        .map(|l| l.to_string())
+        .collect())
+}
+
+/// Fetch `refspec` from `remote`. Returns false if the remote doesn't have
+/// the ref.
+fn fetch(store: &RecordStore, remote: &str, refspec: &str) -> Result<bool> {
+    let output = git::output(store.repo(), &["fetch", "--quiet", remote, refspec])?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("couldn't find remote ref") {
+        return Ok(false);
+    }
+    Err(anyhow!(
+        "Fetching from {} failed: {}",
+        remote,
+        stderr.trim()
+    ))
+}
+
+fn is_ancestor(store: &RecordStore, a: &str, b: &str) -> bool {
+    git::output(store.repo(), &["merge-base", "--is-ancestor", a, b])
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Files that differ between `base` (the empty tree if `None`) and `tip`,
+/// with their blob at `tip` (`None` if deleted).
+fn changed_blobs(
+    store: &RecordStore,
+    base: Option<&str>,
+    tip: &str,
+) -> Result<BTreeMap<String, Option<String>>> {
+    let repo = store.repo();
+    let Some(base) = base else {
+        // Unrelated histories (both sides ran `arf init`): everything is new
+        let tree = format!("{}^{{tree}}", tip);
+        return Ok(git::run(repo, &["ls-tree", "-r", &tree])?
+            .lines()
+            .filter_map(|line| {
+                let (meta, path) = line.split_once('\t')?;
+                let oid = meta.split(' ').nth(2)?;
+                Some((path.to_string(), Some(oid.to_string())))
+            })
+            .collect());
+    };
+
+    // ":<old mode> <new mode> <old oid> <new oid> <status>\t<path>"
+    let out = git::run(repo, &["diff-tree", "-r", "--no-renames", base, tip])?;
+    let mut changes = BTreeMap::new();
+    for line in out.lines() {
+        let Some((meta, path)) = line.split_once('\t') else {
+            continue;
+        };
+        let fields: Vec<&str> = meta.split(' ').collect();
+        let (Some(oid), Some(status)) = (fields.get(3), fields.get(4)) else {
+            continue;
+        };
+        let blob = (!status.starts_with('D')).then(|| oid.to_string());
+        changes.insert(path.to_string(), blob);
+    }
+    Ok(changes)
+}
+
+/// Move the local ARF branch from `old` to `new`, updating the `.arf/`
+/// worktree along with it when there is one.
+fn advance(store: &RecordStore, old: Option<&str>, new: &str) -> Result<()> {
+    if let Some(dir) = store.worktree() {
+        let status = git::worktree_output(&dir, &["status", "--porcelain"])?;
+        if !status.stdout.is_empty() {
+            return Err(anyhow!(
+                "{} has uncommitted changes; commit them with 'arf doctor --fix' and sync again",
+                dir.display()
+            ));
+        }
+        let output = git::worktree_output(&dir, &["merge", "--ff-only", "--quiet", new])?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "Updating {} failed: {}",
+                dir.display(),
+                stderr.trim()
+            ));
+        }
+        return Ok(());
+    }
+
+    let local_ref = store.config().branch_ref();
+    let old = old
+        .map(|o| o.to_string())
+        .unwrap_or_else(|| "0".repeat(new.len()));
+    git::run(
+        store.repo(),
+        &["update-ref", "-m", "arf sync", &local_ref, new, &old],
+    )
+    .map(|_| ())
+    .map_err(|e| anyhow!("Failed to update {}: {}", local_ref, e))
+}
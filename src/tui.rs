@@ -0,0 +1,568 @@
+use anyhow::{anyhow, Result};
+use arf::{git, ArfRecord, Config, RecordStore};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+};
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+struct CommitInfo {
+    sha: String,
+    short_sha: String,
+    message: String,
+    records: Vec<ArfRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffMode {
+    Hidden,
+    Stat,
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Commits,
+    Diff,
+}
+
+/// What a key press does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Quit,
+    Down,
+    Up,
+    Diff,
+    Focus,
+    PageDown,
+    PageUp,
+}
+
+/// Each action, its `tui.key.*` setting, and its default keys.
+const ACTIONS: &[(Action, &str, &[&str])] = &[
+    (Action::Quit, "tui.key.quit", &["q", "esc"]),
+    (Action::Down, "tui.key.down", &["j", "down"]),
+    (Action::Up, "tui.key.up", &["k", "up"]),
+    (Action::Diff, "tui.key.diff", &["d"]),
+    (Action::Focus, "tui.key.focus", &["tab", "enter"]),
+    (Action::PageDown, "tui.key.pageDown", &["f", "pagedown"]),
+    (Action::PageUp, "tui.key.pageUp", &["b", "pageup"]),
+];
+
+/// Key bindings, from `tui.key.*` or the defaults.
+struct Keys {
+    bindings: Vec<(Action, Vec<(KeyCode, String)>)>,
+}
+
+impl Keys {
+    fn from_config(config: &Config) -> Result<Self> {
+        let mut bindings = Vec::new();
+        for (action, setting, defaults) in ACTIONS {
+            let mut names = config.get_all(setting);
+            if names.is_empty() {
+                names = defaults.iter().map(|k| k.to_string()).collect();
+            }
+            let keys = names
+                .into_iter()
+                .map(|name| match parse_key(&name) {
+                    Some(code) => Ok((code, name)),
+                    None => Err(anyhow!("Unknown key '{}' in {}", name, setting)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            bindings.push((*action, keys));
+        }
+        Ok(Self { bindings })
+    }
+
+    fn action(&self, code: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|(k, _)| *k == code))
+            .map(|(action, _)| *action)
+    }
+
+    /// The first key bound to `action`, for the help bar.
+    fn label(&self, action: Action) -> &str {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .and_then(|(_, keys)| keys.first())
+            .map_or("-", |(_, name)| name.as_str())
+    }
+}
+
+/// A single character, or one of [`arf::config::KEY_NAMES`].
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    Some(match name.to_lowercase().as_str() {
+        "esc" => KeyCode::Esc,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        _ => return None,
+    })
+}
+
+/// Colors, from `tui.theme`.
+#[derive(Debug, Clone, Copy)]
+struct Theme {
+    /// Border of the focused pane.
+    focused: Style,
+    selected: Style,
+    help_bar: Style,
+    added: Style,
+    removed: Style,
+    hunk: Style,
+    file_header: Style,
+    file_marker: Style,
+}
+
+impl Theme {
+    fn from_config(config: &Config) -> Result<Self> {
+        match config.get("tui.theme").as_deref() {
+            None | Some("dark") => Ok(Self {
+                focused: Style::default().fg(Color::Cyan),
+                selected: Style::default().bg(Color::DarkGray).bold(),
+                help_bar: Style::default().bg(Color::DarkGray),
+                added: Style::default().fg(Color::Green),
+                removed: Style::default().fg(Color::Red),
+                hunk: Style::default().fg(Color::Cyan),
+                file_header: Style::default().fg(Color::Yellow).bold(),
+                file_marker: Style::default().fg(Color::Yellow),
+            }),
+            Some("light") => Ok(Self {
+                focused: Style::default().fg(Color::Blue),
+                selected: Style::default().bg(Color::Gray).fg(Color::Black).bold(),
+                help_bar: Style::default().bg(Color::Gray).fg(Color::Black),
+                added: Style::default().fg(Color::Green),
+                removed: Style::default().fg(Color::Red),
+                hunk: Style::default().fg(Color::Blue),
+                file_header: Style::default().fg(Color::Magenta).bold(),
+                file_marker: Style::default().fg(Color::Magenta),
+            }),
+            Some("plain") => Ok(Self {
+                focused: Style::default().bold(),
+                selected: Style::default().reversed(),
+                help_bar: Style::default().reversed(),
+                added: Style::default(),
+                removed: Style::default(),
+                hunk: Style::default().bold(),
+                file_header: Style::default().bold(),
+                file_marker: Style::default(),
+            }),
+            Some(other) => Err(anyhow!(
+                "Unknown tui.theme '{}' (expected dark, light, or plain)",
+                other
+            )),
+        }
+    }
+}
+
+struct App {
+    repo: PathBuf,
+    commits: Vec<CommitInfo>,
+    list_state: ListState,
+    diff_mode: DiffMode,
+    diff_lines: Vec<DiffLine>,
+    diff_scroll: usize,
+    focus: Focus,
+    should_quit: bool,
+    keys: Keys,
+    theme: Theme,
+}
+
+#[derive(Debug, Clone)]
+struct DiffLine {
+    content: String,
+    style: Style,
+}
+
+impl App {
+    fn new(repo: &Path, commits: Vec<CommitInfo>, keys: Keys, theme: Theme) -> Self {
+        let mut list_state = ListState::default();
+        if !commits.is_empty() {
+            list_state.select(Some(0));
+        }
+        Self {
+            repo: repo.to_path_buf(),
+            commits,
+            list_state,
+            diff_mode: DiffMode::Stat,
+            diff_lines: Vec::new(),
+            diff_scroll: 0,
+            focus: Focus::Commits,
+            should_quit: false,
+            keys,
+            theme,
+        }
+    }
+
+    fn selected_commit(&self) -> Option<&CommitInfo> {
+        self.list_state.selected().and_then(|i| self.commits.get(i))
+    }
+
+    fn next(&mut self) {
+        match self.focus {
+            Focus::Commits => {
+                if self.commits.is_empty() {
+                    return;
+                }
+                let i = match self.list_state.selected() {
+                    Some(i) => (i + 1) % self.commits.len(),
+                    None => 0,
+                };
+                self.list_state.select(Some(i));
+                self.diff_scroll = 0;
+                self.update_diff();
+            }
+            Focus::Diff => {
+                if self.diff_scroll < self.diff_lines.len().saturating_sub(1) {
+                    self.diff_scroll += 1;
+                }
+            }
+        }
+    }
+
+    fn previous(&mut self) {
+        match self.focus {
+            Focus::Commits => {
+                if self.commits.is_empty() {
+                    return;
+                }
+                let i = match self.list_state.selected() {
+                    Some(i) => {
+                        if i == 0 {
+                            self.commits.len() - 1
+                        } else {
+                            i - 1
+                        }
+                    }
+                    None => 0,
+                };
+                self.list_state.select(Some(i));
+                self.diff_scroll = 0;
+                self.update_diff();
+            }
+            Focus::Diff => {
+                self.diff_scroll = self.diff_scroll.saturating_sub(1);
+            }
+        }
+    }
+
+    fn toggle_focus(&mut self) {
+        if self.diff_mode != DiffMode::Hidden {
+            self.focus = match self.focus {
+                Focus::Commits => Focus::Diff,
+                Focus::Diff => Focus::Commits,
+            };
+        }
+    }
+
+    fn toggle_diff(&mut self) {
+        self.diff_mode = match self.diff_mode {
+            DiffMode::Hidden => DiffMode::Stat,
+            DiffMode::Stat => DiffMode::Full,
+            DiffMode::Full => DiffMode::Hidden,
+        };
+        if self.diff_mode == DiffMode::Hidden {
+            self.focus = Focus::Commits;
+        }
+        self.diff_scroll = 0;
+        self.update_diff();
+    }
+
+    fn page_down(&mut self) {
+        if self.focus == Focus::Diff {
+            self.diff_scroll = (self.diff_scroll + 10).min(self.diff_lines.len().saturating_sub(1));
+        }
+    }
+
+    fn page_up(&mut self) {
+        if self.focus == Focus::Diff {
+            self.diff_scroll = self.diff_scroll.saturating_sub(10);
+        }
+    }
+
+    fn update_diff(&mut self) {
+        self.diff_lines.clear();
+
+        if self.diff_mode == DiffMode::Hidden {
+            return;
+        }
+
+        let Some(commit) = self.selected_commit() else {
+            return;
+        };
+
+        let args = if self.diff_mode == DiffMode::Full {
+            vec!["show", "--format=", &commit.sha]
+        } else {
+            vec!["show", "--stat", "--format=", &commit.sha]
+        };
+
+        let output = git::output(&self.repo, &args);
+        let content = match output {
+            Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
+            _ => "Failed to get diff".to_string(),
+        };
+
+        // Parse lines with syntax highlighting
+        let theme = self.theme;
+        for line in content.lines() {
+            let (style, display) = if line.starts_with('+') && !line.starts_with("+++") {
+                (theme.added, line.to_string())
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                (theme.removed, line.to_string())
+            } else if line.starts_with("@@") {
+                (theme.hunk, line.to_string())
+            } else if line.starts_with("diff ") || line.starts_with("index ") {
+                (theme.file_header, line.to_string())
+            } else if line.starts_with("+++") || line.starts_with("---") {
+                (theme.file_marker, line.to_string())
+            } else {
+                (Style::default(), line.to_string())
+            };
+
+            self.diff_lines.push(DiffLine {
+                content: display,
+                style,
+            });
+        }
+    }
+}
+
+pub fn run(repo: &Path) -> Result<()> {
+    let store = RecordStore::open(repo)?;
+    // Check the settings before taking over the terminal
+    let keys = Keys::from_config(store.config())?;
+    let theme = Theme::from_config(store.config())?;
+
+    let commits: Vec<CommitInfo> = git::log(repo, 50)?
+        .into_iter()
+        .map(|c| CommitInfo {
+            records: store
+                .load_resolved(&c.sha)
+                .into_iter()
+                .map(|r| r.record)
+                .collect(),
+            sha: c.sha,
+            short_sha: c.short_sha,
+            message: c.subject,
+        })
+        .collect();
+
+    if commits.is_empty() {
+        println!("No commits found.");
+        return Ok(());
+    }
+
+    // Setup terminal
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let mut app = App::new(repo, commits, keys, theme);
+    app.update_diff();
+
+    // Main loop
+    loop {
+        terminal.draw(|frame| ui(frame, &mut app))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                match app.keys.action(key.code) {
+                    Some(Action::Quit) => app.should_quit = true,
+                    Some(Action::Down) => app.next(),
+                    Some(Action::Up) => app.previous(),
+                    Some(Action::Diff) => app.toggle_diff(),
+                    Some(Action::Focus) => app.toggle_focus(),
+                    Some(Action::PageDown) => app.page_down(),
+                    Some(Action::PageUp) => app.page_up(),
+                    None => {}
+                }
+            }
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+
+    // Restore terminal
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+
+    Ok(())
+}
+
+fn ui(frame: &mut Frame, app: &mut App) {
+    let has_diff = app.diff_mode != DiffMode::Hidden;
+
+    // Border styles based on focus
+    let focused_border = app.theme.focused;
+    let unfocused_border = Style::default();
+
+    // Main layout
+    let main_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(if has_diff {
+            vec![Constraint::Percentage(50), Constraint::Percentage(50)]
+        } else {
+            vec![Constraint::Percentage(100)]
+        })
+        .split(frame.area());
+
+    // Top section: commits + reasoning
+    let top_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(main_chunks[0]);
+
+    // Commits list
+    let items: Vec<ListItem> = app
+        .commits
+        .iter()
+        .map(|c| {
+            let has_arf = if c.records.is_empty() { " " } else { "●" };
+            ListItem::new(format!("{} {} {}", has_arf, c.short_sha, c.message))
+        })
+        .collect();
+
+    let commits_border = if app.focus == Focus::Commits {
+        focused_border
+    } else {
+        unfocused_border
+    };
+
+    let commits_list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(commits_border)
+                .title(" Commits "),
+        )
+        .highlight_style(app.theme.selected)
+        .highlight_symbol("→ ");
+
+    frame.render_stateful_widget(commits_list, top_chunks[0], &mut app.list_state);
+
+    // Reasoning panel
+    let reasoning_text = if let Some(commit) = app.selected_commit() {
+        if commit.records.is_empty() {
+            "(no ARF record for this commit)".to_string()
+        } else {
+            commit
+                .records
+                .iter()
+                .map(|r| {
+                    let mut s = format!("what: {}\nwhy:  {}", r.what, r.why);
+                    if let Some(ref how) = r.how {
+                        s.push_str(&format!("\nhow:  {}", how));
+                    }
+                    if let Some(ref backup) = r.backup {
+                        s.push_str(&format!("\nback: {}", backup));
+                    }
+                    if let Some(ref outcome) = r.outcome {
+                        s.push_str(&format!("\nout:  {}", outcome));
+                    }
+                    if let Some(ref retraction) = r.retracted {
+                        s.push_str(&format!("\nretracted: {}", retraction));
+                    }
+                    if !r.context.is_empty() {
+                        s.push_str("\ncontext:");
+                        for line in r.context_lines() {
+                            s.push_str(&format!("\n  {}", line));
+                        }
+                    }
+                    s
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n---\n\n")
+        }
+    } else {
+        "No commit selected".to_string()
+    };
+
+    let reasoning = Paragraph::new(reasoning_text)
+        .block(Block::default().borders(Borders::ALL).title(" Reasoning "))
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(reasoning, top_chunks[1]);
+
+    // Diff panel (if visible)
+    if has_diff {
+        let diff_border = if app.focus == Focus::Diff {
+            focused_border
+        } else {
+            unfocused_border
+        };
+
+        let diff_title = match app.diff_mode {
+            DiffMode::Stat => " Diff (stat) ",
+            DiffMode::Full => " Diff (full) ",
+            DiffMode::Hidden => "",
+        };
+
+        // Build styled lines from diff_lines
+        let lines: Vec<Line> = app
+            .diff_lines
+            .iter()
+            .skip(app.diff_scroll)
+            .map(|dl| Line::from(Span::styled(dl.content.clone(), dl.style)))
+            .collect();
+
+        let scroll_info = if !app.diff_lines.is_empty() {
+            format!(" [{}/{}] ", app.diff_scroll + 1, app.diff_lines.len())
+        } else {
+            String::new()
+        };
+
+        let diff = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(diff_border)
+                .title(format!("{}{}", diff_title, scroll_info)),
+        );
+
+        frame.render_widget(diff, main_chunks[1]);
+    }
+
+    // Help bar at bottom
+    let keys = &app.keys;
+    let help = format!(
+        " {}: quit | {}/{}: scroll | {}: focus | {}: toggle diff | {}/{}: page ",
+        keys.label(Action::Quit),
+        keys.label(Action::Down),
+        keys.label(Action::Up),
+        keys.label(Action::Focus),
+        keys.label(Action::Diff),
+        keys.label(Action::PageDown),
+        keys.label(Action::PageUp),
+    );
+    let help_area = Rect {
+        x: 0,
+        y: frame.area().height - 1,
+        width: frame.area().width,
+        height: 1,
+    };
+    frame.render_widget(Paragraph::new(help).style(app.theme.help_bar), help_area);
+}
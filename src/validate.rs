@@ -0,0 +1,523 @@
+//! Checking records against the ARF schema (`arf validate`).
+//!
+//! Schema checks work on the raw TOML, so a file that wouldn't even load as
+//! an [`ArfRecord`] still gets one message per broken field. On top of that,
+//! a lint layer flags records that are valid but say little, following the
+//! quality rules in SPEC.md ("Not 'I will analyze' but ..."), and records
+//! missing a field the repository requires. Lint rules are configured like
+//! any other setting (see [`crate::config`]):
+//!
+//! ```text
+//! lint.disable     rule to turn off (vague-what, short-what, what-is-why, required)
+//! lint.vaguePhrase extra phrase that makes a `what` vague
+//! lint.minWords    fewest words a `what` may have (default 3)
+//! record.require   field records must fill in besides what and why
+//! ```
+
+use anyhow::Result;
+use chrono::DateTime;
+use std::fmt;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::git;
+use crate::record::{ArfRecord, RECORD_SCHEMA_VERSION};
+use crate::store::{RecordStore, Storage};
+
+/// Top-level fields of schema version 1.
+const FIELDS: &[&str] = &[
+    "schema_version",
+    "id",
+    "what",
+    "why",
+    "how",
+    "backup",
+    "outcome",
+    "timestamp",
+    "commit",
+    "agent",
+    "retracted",
+    "context",
+];
+
+const OUTCOMES: &[&str] = &["success", "failure", "partial"];
+
+/// Openings that describe investigating rather than acting.
+const VAGUE_PHRASES: &[&str] = &[
+    "i will",
+    "i'll",
+    "i am going to",
+    "i'm going to",
+    "let me",
+    "going to",
+    "trying to",
+    "try to",
+    "analyze",
+    "analyse",
+    "investigate",
+    "look into",
+    "explore",
+    "various",
+    "misc",
+    "some changes",
+    "wip",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Lint findings: the record is valid but could say more.
+    Warning,
+    /// Schema violations.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// One thing wrong with a record.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    /// Field the problem is about, if any.
+    pub field: Option<String>,
+    pub message: String,
+    /// Lint rule that found it; `None` for schema checks.
+    pub rule: Option<&'static str>,
+}
+
+impl Problem {
+    pub(crate) fn error(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            field: Some(field.to_string()),
+            message: message.into(),
+            rule: None,
+        }
+    }
+
+    /// The whole input failed to parse.
+    pub(crate) fn invalid_toml(error: &toml::de::Error) -> Self {
+        Self {
+            severity: Severity::Error,
+            field: None,
+            message: format!("not valid TOML: {}", error.message()),
+            rule: None,
+        }
+    }
+
+    /// A field the schema doesn't know.
+    pub(crate) fn unknown(field: &str) -> Self {
+        Self {
+            severity: Severity::Warning,
+            field: Some(field.to_string()),
+            message: "unknown field; put extensions under [context]".to_string(),
+            rule: None,
+        }
+    }
+
+    fn lint(rule: &'static str, field: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            field: Some(field.to_string()),
+            message: message.into(),
+            rule: Some(rule),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref field) = self.field {
+            write!(f, "{}: ", field)?;
+        }
+        f.write_str(&self.message)?;
+        if let Some(rule) = self.rule {
+            write!(f, " [{}]", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// The problems with one record in a file or note.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// File path, path on the `arf` branch, or `note on <sha>`.
+    pub source: String,
+    /// Which record in a stream (from 1), or `None` for a single record.
+    pub entry: Option<usize>,
+    /// The record's `what`, when it has one.
+    pub what: Option<String>,
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.problems.iter().any(|p| p.severity == Severity::Error)
+    }
+}
+
+/// Lint rules and their settings.
+#[derive(Debug, Clone)]
+pub struct Lint {
+    /// Phrases that make a `what` vague, lowercase.
+    pub vague_phrases: Vec<String>,
+    /// Fewest words a `what` may have.
+    pub min_words: usize,
+    /// Rules turned off.
+    pub disabled: Vec<String>,
+    /// Fields records must have besides `what` and `why`: `how`, `backup`,
+    /// `outcome`, `agent`, or `context.<key>`.
+    pub required: Vec<String>,
+}
+
+impl Default for Lint {
+    fn default() -> Self {
+        Self {
+            vague_phrases: VAGUE_PHRASES.iter().map(|p| p.to_string()).collect(),
+            min_words: 3,
+            disabled: Vec::new(),
+            required: Vec::new(),
+        }
+    }
+}
+
+impl Lint {
+    /// The default rules, adjusted by `lint.*` and `record.require` in
+    /// `config`.
+    pub fn from_config(config: &Config) -> Self {
+        let get_all = |key: &str| -> Vec<String> {
+            config
+                .get_all(key)
+                .iter()
+                .flat_map(|v| v.split(','))
+                .map(|v| v.trim().to_lowercase())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+
+        let mut lint = Self::default();
+        lint.vague_phrases.extend(get_all("lint.vaguePhrase"));
+        lint.disabled = get_all("lint.disable");
+        lint.required = config.required.clone();
+        if let Some(n) = get_all("lint.minWords").last().and_then(|n| n.parse().ok()) {
+            lint.min_words = n;
+        }
+        lint
+    }
+
+    /// Required fields `record` leaves empty.
+    pub fn missing<'a>(&'a self, record: &ArfRecord) -> Vec<&'a str> {
+        let filled = |s: &Option<String>| s.as_deref().is_some_and(|s| !s.trim().is_empty());
+        self.required
+            .iter()
+            .map(|field| field.as_str())
+            .filter(|field| match *field {
+                "how" => !filled(&record.how),
+                "backup" => !filled(&record.backup),
+                "agent" => !filled(&record.agent),
+                "outcome" => record.outcome.is_none(),
+                other => other
+                    .strip_prefix("context.")
+                    .is_some_and(|key| !record.context.contains_key(key)),
+            })
+            .collect()
+    }
+
+    fn enabled(&self, rule: &str) -> bool {
+        !self.disabled.iter().any(|d| d == rule || d == "all")
+    }
+
+    /// Lint findings for a valid record.
+    pub fn check(&self, record: &ArfRecord) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let what = record.what.trim();
+        let lower = what.to_lowercase();
+
+        if self.enabled("vague-what") {
+            let vague = self.vague_phrases.iter().find(|phrase| {
+                lower == **phrase
+                    || lower
+                        .strip_prefix(phrase.as_str())
+                        .is_some_and(|rest| rest.starts_with(|c: char| !c.is_alphanumeric()))
+            });
+            if let Some(phrase) = vague {
+                problems.push(Problem::lint(
+                    "vague-what",
+                    "what",
+                    format!(
+                        "starts with \"{}\"; name the concrete change instead \
+                         (e.g. \"Add validation to calculate_total()\")",
+                        phrase
+                    ),
+                ));
+            }
+        }
+
+        let words = what.split_whitespace().count();
+        if self.enabled("short-what") && words < self.min_words {
+            problems.push(Problem::lint(
+                "short-what",
+                "what",
+                format!(
+                    "only {} word(s); say what changes and where (at least {})",
+                    words, self.min_words
+                ),
+            ));
+        }
+
+        if self.enabled("what-is-why") && lower == record.why.trim().to_lowercase() {
+            problems.push(Problem::lint(
+                "what-is-why",
+                "why",
+                "repeats 'what'; explain the reasoning behind it",
+            ));
+        }
+
+        if self.enabled("required") {
+            for field in self.missing(record) {
+                problems.push(Problem::lint(
+                    "required",
+                    field,
+                    "missing, and this repository requires it (record.require)",
+                ));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Check one record, given as a TOML table, against the schema. The commit
+/// it names must exist in `repo`, if one is given.
+pub fn check_table(table: &toml::Table, repo: Option<&Path>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    check_schema_version(table, RECORD_SCHEMA_VERSION, &mut problems);
+    check_required_text(table, &["what", "why"], &mut problems);
+    check_optional_text(
+        table,
+        &["id", "how", "backup", "agent", "commit"],
+        &mut problems,
+    );
+
+    match table.get("timestamp") {
+        None => problems.push(Problem::error("timestamp", "required field is missing")),
+        Some(toml::Value::String(s)) => {
+            if let Err(e) = DateTime::parse_from_rfc3339(s) {
+                problems.push(Problem::error(
+                    "timestamp",
+                    format!("'{}' is not RFC 3339: {}", s, e),
+                ));
+            }
+        }
+        Some(_) => problems.push(Problem::error("timestamp", "must be an RFC 3339 string")),
+    }
+
+    let check_status = |status: Option<&toml::Value>, problems: &mut Vec<Problem>| match status {
+        Some(toml::Value::String(s)) if OUTCOMES.contains(&s.as_str()) => {}
+        Some(toml::Value::String(s)) => problems.push(Problem::error(
+            "outcome",
+            format!("'{}' is not one of {}", s, OUTCOMES.join(", ")),
+        )),
+        _ => problems.push(Problem::error(
+            "outcome",
+            format!("status must be one of {}", OUTCOMES.join(", ")),
+        )),
+    };
+    match table.get("outcome") {
+        None => {}
+        Some(status @ toml::Value::String(_)) => check_status(Some(status), &mut problems),
+        Some(toml::Value::Table(outcome)) => {
+            // Older records spell it `outcome`, as the parser also accepts
+            let status = outcome.get("status").or_else(|| outcome.get("outcome"));
+            check_status(status, &mut problems);
+            if outcome.get("reason").is_some_and(|r| !r.is_str()) {
+                problems.push(Problem::error("outcome", "reason must be a string"));
+            }
+        }
+        Some(_) => problems.push(Problem::error(
+            "outcome",
+            "must be a status or a table with status and reason",
+        )),
+    }
+
+    match table.get("retracted") {
+        None => {}
+        Some(toml::Value::Table(retracted)) => {
+            for key in ["reason", "timestamp"] {
+                if !retracted.get(key).is_some_and(|v| v.is_str()) {
+                    problems.push(Problem::error(
+                        "retracted",
+                        format!("{} must be a string", key),
+                    ));
+                }
+            }
+        }
+        Some(_) => problems.push(Problem::error(
+            "retracted",
+            "must be a table with reason and timestamp",
+        )),
+    }
+
+    if table.get("context").is_some_and(|c| !c.is_table()) {
+        problems.push(Problem::error("context", "must be a table"));
+    }
+
+    if let (Some(repo), Some(toml::Value::String(commit))) = (repo, table.get("commit")) {
+        if git::resolve_commit(repo, commit).is_err() {
+            problems.push(Problem::error(
+                "commit",
+                format!(
+                    "'{}' does not resolve to a commit in this repository",
+                    commit
+                ),
+            ));
+        }
+    }
+
+    check_unknown(table, FIELDS, "", &mut problems);
+
+    problems
+}
+
+/// `schema_version`, when given, must be an integer from 1 to `supported`.
+pub(crate) fn check_schema_version(
+    table: &toml::Table,
+    supported: u32,
+    problems: &mut Vec<Problem>,
+) {
+    match table.get("schema_version") {
+        None => {}
+        Some(toml::Value::Integer(v)) if (1..=supported as i64).contains(v) => {}
+        Some(toml::Value::Integer(v)) => problems.push(Problem::error(
+            "schema_version",
+            format!(
+                "version {} is not supported (this arf reads up to {})",
+                v, supported
+            ),
+        )),
+        Some(_) => problems.push(Problem::error("schema_version", "must be an integer")),
+    }
+}
+
+/// Each of `fields` must be a non-empty string.
+pub(crate) fn check_required_text(
+    table: &toml::Table,
+    fields: &[&str],
+    problems: &mut Vec<Problem>,
+) {
+    for &field in fields {
+        match table.get(field) {
+            None => problems.push(Problem::error(field, "required field is missing")),
+            Some(toml::Value::String(s)) if s.trim().is_empty() => {
+                problems.push(Problem::error(field, "must not be empty"))
+            }
+            Some(toml::Value::String(_)) => {}
+            Some(_) => problems.push(Problem::error(field, "must be a string")),
+        }
+    }
+}
+
+/// Each of `fields` that's present must be a string.
+pub(crate) fn check_optional_text(
+    table: &toml::Table,
+    fields: &[&str],
+    problems: &mut Vec<Problem>,
+) {
+    for &field in fields {
+        if table.get(field).is_some_and(|v| !v.is_str()) {
+            problems.push(Problem::error(field, "must be a string"));
+        }
+    }
+}
+
+/// Warn about keys of `table` not in `known`, naming them `<prefix><key>`.
+pub(crate) fn check_unknown(
+    table: &toml::Table,
+    known: &[&str],
+    prefix: &str,
+    problems: &mut Vec<Problem>,
+) {
+    for key in table.keys() {
+        if !known.contains(&key.as_str()) {
+            problems.push(Problem::unknown(&format!("{}{}", prefix, key)));
+        }
+    }
+}
+
+/// Check every record in a file's `content`: a single record or a TOML
+/// stream. `lint` is skipped when `None`.
+pub fn check_file(
+    source: &str,
+    content: &str,
+    repo: Option<&Path>,
+    lint: Option<&Lint>,
+) -> Vec<Report> {
+    let table: toml::Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(e) => {
+            return vec![Report {
+                source: source.to_string(),
+                entry: None,
+                what: None,
+                problems: vec![Problem::invalid_toml(&e)],
+            }]
+        }
+    };
+
+    let entries: Vec<(Option<usize>, toml::Table)> = match table.get("record") {
+        Some(toml::Value::Array(entries)) => entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (Some(i + 1), e.as_table().cloned().unwrap_or_default()))
+            .collect(),
+        _ => vec![(None, table)],
+    };
+
+    entries
+        .into_iter()
+        .map(|(entry, table)| {
+            let mut problems = check_table(&table, repo);
+            if let (Some(lint), false) =
+                (lint, problems.iter().any(|p| p.severity == Severity::Error))
+            {
+                if let Ok(record) = toml::Value::Table(table.clone()).try_into::<ArfRecord>() {
+                    problems.extend(lint.check(&record));
+                }
+            }
+            Report {
+                source: source.to_string(),
+                entry,
+                what: table.get("what").and_then(|w| w.as_str()).map(String::from),
+                problems,
+            }
+        })
+        .collect()
+}
+
+/// Check every record in the store, including ones that fail to load.
+pub fn check_store(store: &RecordStore, lint: Option<&Lint>) -> Result<Vec<Report>> {
+    let repo = Some(store.repo());
+    let mut reports = Vec::new();
+
+    if store.storage() == Storage::Notes {
+        for (commit, content) in store.notes().read_all()? {
+            let source = format!("note on {}", git::short_sha(&commit));
+            reports.extend(check_file(&source, &content, repo, lint));
+        }
+        return Ok(reports);
+    }
+
+    for (path, content) in store.backend().read_all("records")? {
+        if path.ends_with(".toml") {
+            reports.extend(check_file(&path, &content, repo, lint));
+        }
+    }
+    Ok(reports)
+}
@@ -0,0 +1,143 @@
+//! Scratch git repositories for the integration tests.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A git repository in a fresh temp directory, removed on drop.
+pub struct TempRepo {
+    pub path: PathBuf,
+}
+
+impl TempRepo {
+    /// An empty repository with one commit.
+    pub fn new() -> Self {
+        let repo = Self::empty_dir();
+        repo.git(&["init", "--quiet", "--initial-branch", "main"]);
+        repo.git(&["commit", "--quiet", "--allow-empty", "-m", "Initial commit"]);
+        repo
+    }
+
+    /// A bare repository, for use as a remote.
+    pub fn bare() -> Self {
+        let repo = Self::empty_dir();
+        repo.git(&["init", "--quiet", "--bare", "--initial-branch", "main"]);
+        repo
+    }
+
+    /// A clone of `remote`, which must have a commit.
+    pub fn clone_of(remote: &TempRepo) -> Self {
+        let repo = Self::empty_dir();
+        let source = remote.path.to_string_lossy().to_string();
+        repo.git(&["clone", "--quiet", &source, "."]);
+        repo
+    }
+
+    fn empty_dir() -> Self {
+        let name = format!("arf-test-{}", arf::id::generate().to_lowercase());
+        let path = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self {
+            path: path.canonicalize().expect("canonicalize temp dir"),
+        }
+    }
+
+    /// Run git, panicking on failure, and return its trimmed stdout.
+    pub fn git(&self, args: &[&str]) -> String {
+        run(git(&self.path).args(args))
+    }
+
+    /// Run git in the `.arf/` worktree of the ARF branch.
+    pub fn arf_git(&self, args: &[&str]) -> String {
+        run(git(&self.path.join(".arf")).args(args))
+    }
+
+    /// Paths under `records/` on the `arf` branch.
+    pub fn record_files(&self) -> Vec<String> {
+        let files = self.git(&["ls-tree", "-r", "--name-only", "arf", "records"]);
+        files.lines().map(str::to_string).collect()
+    }
+
+    /// The arf binary, set up to run in this repository.
+    pub fn arf_command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_arf"));
+        isolate(&mut cmd, &self.path);
+        cmd.args(args);
+        cmd
+    }
+
+    /// Run the arf binary.
+    pub fn arf(&self, args: &[&str]) -> Output {
+        self.arf_command(args).output().expect("run arf")
+    }
+
+    /// Run the arf binary, panicking on failure, and return its stdout.
+    pub fn arf_ok(&self, args: &[&str]) -> String {
+        let output = self.arf(args);
+        assert!(
+            output.status.success(),
+            "arf {} failed:\n{}{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    /// Write `content` to `path` inside the repository.
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.path.join(path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).expect("create dir");
+        }
+        std::fs::write(path, content).expect("write file");
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+fn git(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    isolate(&mut cmd, dir);
+    cmd
+}
+
+fn run(cmd: &mut Command) -> String {
+    let output = cmd.output().expect("run git");
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        cmd,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Keep the user's git and arf settings out of the test, and put the arf
+/// under test on PATH for the hooks.
+fn isolate(cmd: &mut Command, dir: &Path) {
+    let bin = Path::new(env!("CARGO_BIN_EXE_arf")).parent().unwrap();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let mut paths = vec![bin.to_path_buf()];
+    paths.extend(std::env::split_paths(&path));
+
+    for (key, _) in std::env::vars_os() {
+        if key.to_string_lossy().starts_with("ARF_") {
+            cmd.env_remove(key);
+        }
+    }
+    cmd.current_dir(dir)
+        .env("PATH", std::env::join_paths(paths).unwrap())
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("RUST_BACKTRACE", "0");
+}
@@ -0,0 +1,89 @@
+//! Layered configuration: `.arfconfig`, git config, and the environment.
+
+mod common;
+
+use common::TempRepo;
+
+fn get(repo: &TempRepo, key: &str) -> String {
+    repo.arf_ok(&["config", "get", key]).trim().to_string()
+}
+
+fn commit_arfconfig(repo: &TempRepo, content: &str) {
+    repo.write(".arf/.arfconfig", content);
+    repo.git(&["-C", ".arf", "add", ".arfconfig"]);
+    repo.git(&["-C", ".arf", "commit", "--quiet", "-m", "Config"]);
+}
+
+#[test]
+fn later_layers_override_earlier_ones() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+
+    repo.arf_ok(&["config", "set", "--shared", "agent", "from-file"]);
+    assert_eq!(get(&repo, "agent"), "from-file");
+
+    repo.arf_ok(&["config", "set", "agent", "from-git"]);
+    assert_eq!(get(&repo, "agent"), "from-git");
+
+    let output = repo
+        .arf_command(&["config", "get", "agent"])
+        .env("ARF_AGENT", "from-env")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "from-env");
+}
+
+#[test]
+fn list_keys_come_from_the_highest_layer() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+    commit_arfconfig(&repo, "[lint]\nvaguePhrase = [\"tidy up\", \"misc\"]\n");
+    let phrases = repo.arf_ok(&["config", "get", "lint.vaguePhrase"]);
+    assert_eq!(phrases.lines().collect::<Vec<_>>(), ["tidy up", "misc"]);
+
+    repo.arf_ok(&["config", "set", "lint.vaguePhrase", "cleanup"]);
+    let phrases = repo.arf_ok(&["config", "get", "lint.vaguePhrase"]);
+    assert_eq!(phrases.lines().collect::<Vec<_>>(), ["cleanup"]);
+
+    let output = repo
+        .arf_command(&["config", "get", "lint.vaguePhrase"])
+        .env("ARF_LINT_VAGUEPHRASE", "wip,stuff")
+        .output()
+        .unwrap();
+    let phrases = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(phrases.lines().collect::<Vec<_>>(), ["wip", "stuff"]);
+}
+
+#[test]
+fn malformed_arfconfig_is_skipped_with_a_warning() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+    repo.arf_ok(&["config", "set", "agent", "from-git"]);
+    commit_arfconfig(&repo, "agent = [unclosed\n");
+
+    let output = repo.arf(&["config", "get", "agent"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "from-git");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Warning: Invalid .arfconfig").count(), 1);
+
+    let (what, why) = ("Add the config loader", "Settings were hard-coded");
+    repo.arf_ok(&["record", "--what", what, "--why", why]);
+    assert!(repo.arf_ok(&["log"]).contains(what));
+}
+
+#[test]
+fn bad_shared_storage_falls_back_with_a_warning() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+    // Only the file says so: git config would win
+    repo.git(&["config", "--unset", "arf.storage"]);
+    commit_arfconfig(&repo, "storage = \"cloud\"\n");
+
+    let (what, why) = ("Add the storage fallback", "A typo locked everyone out");
+    let output = repo.arf(&["record", "--what", what, "--why", why]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Unknown storage 'cloud'").count(), 1);
+    assert!(repo.arf_ok(&["log"]).contains(what));
+}
@@ -0,0 +1,104 @@
+//! `arf doctor --fix` on the ARF branch.
+
+mod common;
+
+use common::TempRepo;
+
+/// A repository with one record, checked out in `.arf/`.
+fn recorded() -> TempRepo {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+    let (what, why) = ("Add the upload parser", "Input was unread");
+    repo.arf_ok(&["record", "--what", what, "--why", why]);
+    repo
+}
+
+/// Report problems, then fix them all.
+fn doctor_fixes(repo: &TempRepo, problem: &str) -> String {
+    let output = repo.arf(&["doctor"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(problem));
+
+    let fixed = repo.arf_ok(&["doctor", "--fix"]);
+    assert!(fixed.contains("✓ Fixed:"));
+    assert!(fixed.contains("✓ No problems left"));
+    fixed
+}
+
+#[test]
+fn fix_sets_mismatched_commit_fields() {
+    let repo = recorded();
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    repo.git(&["commit", "--quiet", "--allow-empty", "-m", "Second"]);
+    let second = repo.git(&["rev-parse", "HEAD"]);
+
+    let path = repo.record_files().remove(0);
+    let file = repo.path.join(".arf").join(&path);
+    let content = std::fs::read_to_string(&file).unwrap();
+    std::fs::write(&file, content.replace(&head, &second)).unwrap();
+    repo.arf_git(&["commit", "--quiet", "-am", "Point at the wrong commit"]);
+
+    doctor_fixes(&repo, "commit field is");
+    let content = repo.git(&["show", &format!("arf:{}", path)]);
+    assert!(content.contains(&format!("commit = \"{}\"", head)));
+}
+
+#[test]
+fn fix_migrates_short_sha_directories() {
+    let repo = recorded();
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    let (from, to) = (
+        format!("records/{}", head),
+        format!("records/{}", &head[..8]),
+    );
+    repo.arf_git(&["mv", &from, &to]);
+    repo.arf_git(&["commit", "--quiet", "-m", "Short SHA"]);
+
+    doctor_fixes(&repo, "keyed by short SHA");
+    let files = repo.record_files();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].starts_with(&format!("records/{}/", head)));
+}
+
+#[test]
+fn fix_commits_leftover_changes() {
+    let repo = recorded();
+    let path = repo.record_files().remove(0);
+    let file = repo.path.join(".arf").join(&path);
+    let content = std::fs::read_to_string(&file).unwrap();
+    std::fs::write(
+        &file,
+        content.replace("Input was unread", "Input was ignored"),
+    )
+    .unwrap();
+
+    doctor_fixes(&repo, "uncommitted changes in .arf/");
+    assert_eq!(repo.arf_git(&["status", "--short"]), "");
+    let content = repo.git(&["show", &format!("arf:{}", path)]);
+    assert!(content.contains("Input was ignored"));
+}
+
+#[test]
+fn fix_leaves_what_it_cannot_repair() {
+    let repo = recorded();
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    let path = repo.record_files().remove(0);
+    let missing = "0".repeat(40);
+    repo.arf_git(&[
+        "mv",
+        path.rsplit_once('/').unwrap().0,
+        &format!("records/{}", missing),
+    ]);
+    repo.arf_git(&["commit", "--quiet", "-m", "Missing commit"]);
+
+    let output = repo.arf(&["doctor", "--fix"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Nothing could be fixed automatically."));
+    assert!(stdout.contains("Needs attention (1):"));
+    assert!(stdout.contains("commit does not exist"));
+    // The record still names the commit that does exist
+    let moved = repo.record_files().remove(0);
+    let content = repo.git(&["show", &format!("arf:{}", moved)]);
+    assert!(content.contains(&format!("commit = \"{}\"", head)));
+}
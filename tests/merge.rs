@@ -0,0 +1,154 @@
+//! `arf sync` pulling records both clones changed, in both storages.
+
+mod common;
+
+use arf::{OutcomeStatus, RecordStore};
+use common::TempRepo;
+
+/// Two clones of one remote, sharing one record.
+struct Pair {
+    _remote: TempRepo,
+    first: TempRepo,
+    second: TempRepo,
+    id: String,
+}
+
+fn pair(storage: &str) -> Pair {
+    let remote = TempRepo::bare();
+    let first = TempRepo::new();
+    first.git(&["remote", "add", "origin", &remote.path.to_string_lossy()]);
+    first.git(&["push", "--quiet", "origin", "main"]);
+    first.arf_ok(&["init", "--storage", storage]);
+    let (what, why) = ("Add the merge step", "Clones drifted apart");
+    first.arf_ok(&["record", "--what", what, "--why", why]);
+    first.arf_ok(&["sync", "--push"]);
+
+    let second = TempRepo::clone_of(&remote);
+    second.arf_ok(&["init", "--storage", storage]);
+    second.arf_ok(&["sync", "--pull"]);
+
+    let records = RecordStore::open(&first.path).unwrap().list().unwrap();
+    let id = records[0].record.id.clone().unwrap();
+    Pair {
+        _remote: remote,
+        first,
+        second,
+        id,
+    }
+}
+
+fn outcome(repo: &TempRepo, id: &str) -> Option<OutcomeStatus> {
+    let store = RecordStore::open(&repo.path).unwrap();
+    store.find(id).unwrap().record.outcome.map(|o| o.status)
+}
+
+/// Both clones set a different outcome on the shared record.
+fn conflicting(storage: &str) -> Pair {
+    let pair = pair(storage);
+    pair.first
+        .arf_ok(&["outcome", &pair.id, "--status", "success"]);
+    pair.first.arf_ok(&["sync", "--push"]);
+    pair.second
+        .arf_ok(&["outcome", &pair.id, "--status", "failure"]);
+    pair
+}
+
+fn diverged_records_merge(storage: &str) {
+    let pair = pair(storage);
+    let (what, why) = ("Add the first extra", "First clone needed it");
+    pair.first.arf_ok(&["record", "--what", what, "--why", why]);
+    pair.first
+        .arf_ok(&["outcome", &pair.id, "--status", "partial"]);
+    pair.first.arf_ok(&["sync", "--push"]);
+    let (what, why) = ("Add the second extra", "Second clone needed it");
+    pair.second
+        .arf_ok(&["record", "--what", what, "--why", why]);
+
+    let stdout = pair.second.arf_ok(&["sync", "--pull"]);
+    assert!(stdout.contains("Merged 2 change(s)"), "{}", stdout);
+    let store = RecordStore::open(&pair.second.path).unwrap();
+    assert_eq!(store.list().unwrap().len(), 3);
+    // Changed on one side only, so that side's version wins
+    assert_eq!(
+        outcome(&pair.second, &pair.id),
+        Some(OutcomeStatus::Partial)
+    );
+}
+
+fn conflicts_stop_the_pull(storage: &str) {
+    let pair = conflicting(storage);
+
+    let output = pair.second.arf(&["sync", "--pull"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Pull stopped: 1 record(s)"), "{}", stdout);
+    assert!(stdout.contains(&pair.id));
+    assert_eq!(
+        outcome(&pair.second, &pair.id),
+        Some(OutcomeStatus::Failure)
+    );
+}
+
+fn prefer_settles_conflicts(storage: &str, prefer: &str, expected: OutcomeStatus) {
+    let pair = conflicting(storage);
+
+    let stdout = pair.second.arf_ok(&["sync", "--pull", "--prefer", prefer]);
+    // Taking the remote's version counts as a change pulled in
+    let taken = if prefer == "remote" { 1 } else { 0 };
+    assert!(
+        stdout.contains(&format!("Merged {} change(s)", taken)),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!("Kept the {} version of 1 record(s)", prefer)),
+        "{}",
+        stdout
+    );
+    let store = RecordStore::open(&pair.second.path).unwrap();
+    assert_eq!(store.list().unwrap().len(), 1);
+    assert_eq!(outcome(&pair.second, &pair.id), Some(expected));
+
+    // The merge joined the histories, so the result pushes cleanly
+    pair.second.arf_ok(&["sync", "--push"]);
+}
+
+#[test]
+fn branch_diverged_records_merge() {
+    diverged_records_merge("branch");
+}
+
+#[test]
+fn branch_conflicts_stop_the_pull() {
+    conflicts_stop_the_pull("branch");
+}
+
+#[test]
+fn branch_prefer_local() {
+    prefer_settles_conflicts("branch", "local", OutcomeStatus::Failure);
+}
+
+#[test]
+fn branch_prefer_remote() {
+    prefer_settles_conflicts("branch", "remote", OutcomeStatus::Success);
+}
+
+#[test]
+fn notes_diverged_records_merge() {
+    diverged_records_merge("notes");
+}
+
+#[test]
+fn notes_conflicts_stop_the_pull() {
+    conflicts_stop_the_pull("notes");
+}
+
+#[test]
+fn notes_prefer_local() {
+    prefer_settles_conflicts("notes", "local", OutcomeStatus::Failure);
+}
+
+#[test]
+fn notes_prefer_remote() {
+    prefer_settles_conflicts("notes", "remote", OutcomeStatus::Success);
+}
@@ -0,0 +1,66 @@
+//! `arf migrate` from short-SHA record directories.
+
+mod common;
+
+use common::TempRepo;
+
+/// A repository whose one record is stored under `records/<dir>`.
+fn stored_under(dir: impl Fn(&str) -> String) -> TempRepo {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+    let (what, why) = ("Add the upload parser", "Input was unread");
+    repo.arf_ok(&["record", "--what", what, "--why", why]);
+
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    let (from, to) = (
+        format!("records/{}", head),
+        format!("records/{}", dir(&head)),
+    );
+    repo.arf_git(&["mv", &from, &to]);
+    repo.arf_git(&["commit", "--quiet", "-m", "Legacy layout"]);
+    repo
+}
+
+#[test]
+fn moves_short_sha_directories() {
+    let repo = stored_under(|head| head[..7].to_string());
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    let before = repo.record_files();
+
+    let dry_run = repo.arf_ok(&["migrate", "--dry-run"]);
+    assert!(dry_run.contains("Would migrate 1 record(s)"));
+    assert_eq!(repo.record_files(), before);
+
+    let migrated = repo.arf_ok(&["migrate"]);
+    assert!(migrated.contains(&format!("{} -> records/{}/", before[0], head)));
+    assert!(migrated.contains("✓ Migrated 1 record(s)"));
+    let files = repo.record_files();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].starts_with(&format!("records/{}/", head)));
+
+    assert!(repo
+        .arf_ok(&["migrate"])
+        .contains("✓ All records are keyed by full commit SHA"));
+    repo.arf_ok(&["doctor"]);
+}
+
+#[test]
+fn skips_directories_that_name_no_commit() {
+    let repo = stored_under(|_| "fffffff".to_string());
+    let before = repo.record_files();
+
+    let output = repo.arf_ok(&["migrate"]);
+    assert!(output.contains("Skipped (1):"));
+    assert!(output.contains("'fffffff' does not name a commit"));
+    assert!(output.contains("✓ Migrated 0 record(s)"));
+    assert_eq!(repo.record_files(), before);
+}
+
+#[test]
+fn notes_need_no_migration() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init", "--storage", "notes"]);
+
+    let output = repo.arf_ok(&["migrate"]);
+    assert!(output.contains("nothing to migrate"));
+}
@@ -0,0 +1,82 @@
+//! Notes storage across amends and rebases.
+
+mod common;
+
+use arf::hooks;
+use arf::RecordStore;
+use common::TempRepo;
+
+fn notes_repo() -> TempRepo {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init", "--storage", "notes"]);
+    let (what, why) = ("Add the parser", "Input was unread");
+    repo.arf_ok(&["record", "--what", what, "--why", why]);
+    repo
+}
+
+fn amend(repo: &TempRepo) {
+    repo.git(&["commit", "-q", "--amend", "--allow-empty", "-m", "Amend"]);
+}
+
+fn remove_rewrite_hook(repo: &TempRepo) {
+    let hook = hooks::hooks_dir(&repo.path).unwrap().join("post-rewrite");
+    std::fs::remove_file(hook).unwrap();
+}
+
+fn note_count(repo: &TempRepo) -> usize {
+    repo.git(&["notes", "--ref", "arf", "list"]).lines().count()
+}
+
+#[test]
+fn init_installs_post_rewrite_hook() {
+    let repo = notes_repo();
+    assert!(hooks::is_installed(&repo.path, "post-rewrite"));
+}
+
+#[test]
+fn amend_moves_note_with_hook() {
+    let repo = notes_repo();
+    amend(&repo);
+
+    assert_eq!(note_count(&repo), 1);
+    let records = RecordStore::open(&repo.path).unwrap().list().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        records[0].record.commit.as_deref(),
+        Some(repo.git(&["rev-parse", "HEAD"]).as_str())
+    );
+}
+
+#[test]
+fn copied_notes_are_listed_once() {
+    let repo = notes_repo();
+    remove_rewrite_hook(&repo);
+    amend(&repo);
+
+    // git copied the note and left the original on the old commit
+    assert_eq!(note_count(&repo), 2);
+
+    let store = RecordStore::open(&repo.path).unwrap();
+    let records = store.list().unwrap();
+    assert_eq!(records.len(), 1);
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    assert_eq!(records[0].record.commit.as_deref(), Some(head.as_str()));
+
+    let id = records[0].record.id.clone().unwrap();
+    let found = store.find(&id[..8]).unwrap();
+    assert_eq!(found.record.commit.as_deref(), Some(head.as_str()));
+}
+
+#[test]
+fn doctor_fix_drops_copied_notes() {
+    let repo = notes_repo();
+    remove_rewrite_hook(&repo);
+    amend(&repo);
+
+    let output = repo.arf(&["doctor"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("left behind by a rewrite"));
+
+    repo.arf_ok(&["doctor", "--fix"]);
+    assert_eq!(note_count(&repo), 1);
+}
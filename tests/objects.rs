@@ -0,0 +1,59 @@
+//! The ARF branch without a worktree: `init --no-checkout` and bare
+//! repositories, where records are written with git plumbing only.
+
+mod common;
+
+use common::TempRepo;
+
+fn branch_files(repo: &TempRepo) -> Vec<String> {
+    let files = repo.git(&["ls-tree", "-r", "--name-only", "arf"]);
+    files.lines().map(str::to_string).collect()
+}
+
+fn record(repo: &TempRepo, what: &str) {
+    repo.arf_ok(&["record", "--what", what, "--why", "The records need a home"]);
+}
+
+#[test]
+fn no_checkout_leaves_the_worktree_alone() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init", "--no-checkout"]);
+    repo.write("staged.txt", "staged\n");
+    repo.git(&["add", "staged.txt"]);
+
+    record(&repo, "Add the upload parser");
+
+    // No worktree for the branch, and the index and HEAD are untouched
+    assert_eq!(repo.git(&["worktree", "list"]).lines().count(), 1);
+    assert_eq!(repo.git(&["status", "--short"]), "A  staged.txt");
+    assert_eq!(repo.git(&["rev-list", "--count", "HEAD"]), "1");
+
+    let head = repo.git(&["rev-parse", "HEAD"]);
+    let records: Vec<_> = branch_files(&repo)
+        .into_iter()
+        .filter(|f| f.starts_with("records/"))
+        .collect();
+    assert_eq!(records.len(), 1);
+    assert!(records[0].starts_with(&format!("records/{}/", head)));
+    assert!(repo.arf_ok(&["log"]).contains("Add the upload parser"));
+}
+
+#[test]
+fn bare_repository_reads_and_writes() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init", "--no-checkout"]);
+    record(&repo, "Add the upload parser");
+
+    let bare = TempRepo::bare();
+    let url = bare.path.to_string_lossy().to_string();
+    repo.git(&["push", "--quiet", &url, "main", "arf"]);
+
+    assert!(bare.arf_ok(&["log"]).contains("Add the upload parser"));
+    let (what, why) = ("Record from the bare repository", "It has no worktree");
+    bare.arf_ok(&["record", "--what", what, "--why", why, "--commit", "main"]);
+
+    let log = bare.arf_ok(&["log"]);
+    assert!(log.contains("Add the upload parser"));
+    assert!(log.contains(what));
+    assert_eq!(branch_files(&bare).len(), 3);
+}
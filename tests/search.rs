@@ -0,0 +1,44 @@
+//! `arf search` keeping its index in step with the store.
+
+mod common;
+
+use arf::hooks;
+use common::TempRepo;
+
+fn record(repo: &TempRepo, what: &str, why: &str) {
+    repo.arf_ok(&["record", "--what", what, "--why", why]);
+}
+
+#[test]
+fn index_picks_up_new_records() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init"]);
+    record(&repo, "Add the tokenizer", "Search split words badly");
+    assert!(repo
+        .arf_ok(&["search", "tokenizer"])
+        .contains("(1 of 1 records)"));
+
+    record(&repo, "Tune the tokenizer", "Underscores split identifiers");
+    let stdout = repo.arf_ok(&["search", "tokenizer"]);
+    assert!(stdout.contains("(2 of 2 records)"), "{}", stdout);
+}
+
+#[test]
+fn copied_notes_leave_the_index_on_update() {
+    let repo = TempRepo::new();
+    repo.arf_ok(&["init", "--storage", "notes"]);
+    record(&repo, "Add the tokenizer", "Search split words badly");
+    assert!(repo
+        .arf_ok(&["search", "tokenizer"])
+        .contains("(1 of 1 records)"));
+
+    // git copies the note on amend and, without the hook, keeps the original
+    let hook = hooks::hooks_dir(&repo.path).unwrap().join("post-rewrite");
+    std::fs::remove_file(hook).unwrap();
+    repo.git(&["commit", "-q", "--amend", "--allow-empty", "-m", "Amend"]);
+
+    let updated = repo.arf_ok(&["search", "tokenizer"]);
+    let rebuilt = repo.arf_ok(&["search", "--rebuild", "tokenizer"]);
+    assert!(updated.contains("(1 of 1 records)"), "{}", updated);
+    assert_eq!(updated, rebuilt);
+}
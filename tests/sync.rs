@@ -0,0 +1,68 @@
+//! `arf sync` pushing to remotes.
+
+mod common;
+
+use common::TempRepo;
+
+/// A repository with a recorded commit and `remote` as origin.
+fn recorded(remote: &str) -> TempRepo {
+    let repo = TempRepo::new();
+    repo.git(&["remote", "add", "origin", remote]);
+    repo.arf_ok(&["init"]);
+    let (what, why) = ("Add the sync command", "Records never left the machine");
+    repo.arf_ok(&["record", "--what", what, "--why", why]);
+    repo
+}
+
+fn remote_has_branch(remote: &TempRepo) -> bool {
+    !remote.git(&["branch", "--list", "arf"]).is_empty()
+}
+
+#[test]
+fn push_succeeds() {
+    let remote = TempRepo::bare();
+    let repo = recorded(&remote.path.to_string_lossy());
+
+    repo.arf_ok(&["sync", "--push"]);
+    assert!(remote_has_branch(&remote));
+}
+
+#[test]
+fn failed_push_exits_non_zero() {
+    let repo = recorded("/nonexistent/arf-remote");
+
+    let output = repo.arf(&["sync", "--push"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Pushing to origin failed"));
+}
+
+#[test]
+fn rejected_push_exits_non_zero() {
+    let remote = TempRepo::bare();
+    let url = remote.path.to_string_lossy().to_string();
+
+    // Two clones start ARF branches of their own; the second push can't
+    // fast-forward
+    let first = recorded(&url);
+    let second = recorded(&url);
+    first.arf_ok(&["sync", "--push"]);
+
+    let output = second.arf(&["sync", "--push"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Push rejected"));
+}
+
+#[test]
+fn failing_mirror_exits_non_zero() {
+    let remote = TempRepo::bare();
+    let repo = recorded(&remote.path.to_string_lossy());
+    repo.git(&["remote", "add", "backup", "/nonexistent/arf-mirror"]);
+    repo.arf_ok(&["config", "set", "mirror", "backup"]);
+
+    let output = repo.arf(&["sync", "--push"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Pushed to 1 of 2 remote(s); failed: backup"));
+    // The working remote still gets the records
+    assert!(remote_has_branch(&remote));
+}
@@ -0,0 +1,50 @@
+//! `arf validate` against record files.
+
+mod common;
+
+use common::TempRepo;
+
+fn record(repo: &TempRepo, outcome: &str) -> String {
+    format!(
+        r#"what = "Add retries to the upload client"
+why = "Uploads fail on flaky networks"
+commit = "{}"
+timestamp = "2026-01-01T00:00:00Z"
+
+{}
+"#,
+        repo.git(&["rev-parse", "HEAD"]),
+        outcome
+    )
+}
+
+#[test]
+fn outcome_table_accepts_status() {
+    let repo = TempRepo::new();
+    let outcome = "[outcome]\nstatus = \"success\"\nreason = \"Tested\"";
+    repo.write("record.toml", &record(&repo, outcome));
+
+    repo.arf_ok(&["validate", "--strict", "record.toml"]);
+}
+
+#[test]
+fn outcome_table_accepts_outcome_alias() {
+    let repo = TempRepo::new();
+    let outcome = "[outcome]\noutcome = \"partial\"\nreason = \"Half done\"";
+    repo.write("record.toml", &record(&repo, outcome));
+
+    repo.arf_ok(&["validate", "--strict", "record.toml"]);
+}
+
+#[test]
+fn outcome_alias_is_still_checked() {
+    let repo = TempRepo::new();
+    repo.write(
+        "record.toml",
+        &record(&repo, "[outcome]\noutcome = \"sorta\""),
+    );
+
+    let output = repo.arf(&["validate", "record.toml"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("'sorta' is not one of"));
+}